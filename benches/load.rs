@@ -0,0 +1,104 @@
+//! Benchmarks for parsing RDFXML into statement rows,
+//! and for loading it into an in-memory database.
+//!
+//! Run with `cargo bench`.
+use std::time::Duration;
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use rusqlite::Connection;
+
+use rdftab::{default_prefixes, load, rdf_to_rows, Options, SqliteSink, DEFAULT_BATCH_SIZE};
+
+/// The number of classes in the medium and large fixtures.
+const SIZES: [usize; 2] = [1_000, 10_000];
+
+/// Generate an ontology with `classes` classes, each with a label,
+/// an existential restriction, and an annotation axiom on the label.
+fn fixture(classes: usize) -> String {
+    let mut rdfxml = String::from(r#"<?xml version="1.0"?>
+<rdf:RDF xmlns:ex="http://example.com/"
+     xmlns:owl="http://www.w3.org/2002/07/owl#"
+     xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#"
+     xmlns:rdfs="http://www.w3.org/2000/01/rdf-schema#">
+"#);
+    for i in 0..classes {
+        rdfxml.push_str(&format!(r#"  <owl:Class rdf:about="http://example.com/class-{0}">
+    <rdfs:label xml:lang="en">Class {0}</rdfs:label>
+    <rdfs:subClassOf>
+      <owl:Restriction>
+        <owl:onProperty rdf:resource="http://example.com/part-of"/>
+        <owl:someValuesFrom rdf:resource="http://example.com/class-{1}"/>
+      </owl:Restriction>
+    </rdfs:subClassOf>
+  </owl:Class>
+  <owl:Axiom>
+    <owl:annotatedSource rdf:resource="http://example.com/class-{0}"/>
+    <owl:annotatedProperty rdf:resource="http://www.w3.org/2000/01/rdf-schema#label"/>
+    <owl:annotatedTarget xml:lang="en">Class {0}</owl:annotatedTarget>
+    <rdfs:comment>Generated</rdfs:comment>
+  </owl:Axiom>
+"#, i, i / 2));
+    }
+    rdfxml.push_str("</rdf:RDF>\n");
+    rdfxml
+}
+
+fn parse(c: &mut Criterion) {
+    let prefixes = default_prefixes();
+    let mut group = c.benchmark_group("parse");
+    group.sample_size(10);
+    for &size in &SIZES {
+        let input = fixture(size);
+        group.throughput(Throughput::Bytes(input.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &input, |b, input| {
+            b.iter(|| rdf_to_rows(black_box(input), &prefixes).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn insert(c: &mut Criterion) {
+    log::set_max_level(log::LevelFilter::Error);
+    let prefixes = default_prefixes();
+    let options = Options::default();
+    let mut group = c.benchmark_group("insert");
+    group.sample_size(10);
+    for &size in &SIZES {
+        let input = fixture(size);
+        group.throughput(Throughput::Bytes(input.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &input, |b, input| {
+            b.iter(|| {
+                let conn = Connection::open_in_memory().unwrap();
+                let mut sink = SqliteSink::new(conn, Duration::from_secs(1)).unwrap();
+                let inputs = vec![(input.as_bytes(), "file:bench.owl".to_string())];
+                load(inputs, &prefixes, &options, &mut sink).unwrap();
+            })
+        });
+    }
+    group.finish();
+}
+
+/// Load the large fixture with `--batch-size` values around `DEFAULT_BATCH_SIZE`.
+fn batch_size(c: &mut Criterion) {
+    log::set_max_level(log::LevelFilter::Error);
+    let prefixes = default_prefixes();
+    let input = fixture(SIZES[1]);
+    let mut group = c.benchmark_group("batch_size");
+    group.sample_size(10);
+    group.throughput(Throughput::Bytes(input.len() as u64));
+    for &batch_size in &[50, DEFAULT_BATCH_SIZE, 5_000] {
+        let options = Options { batch_size, ..Options::default() };
+        group.bench_with_input(BenchmarkId::from_parameter(batch_size), &input, |b, input| {
+            b.iter(|| {
+                let conn = Connection::open_in_memory().unwrap();
+                let mut sink = SqliteSink::new(conn, Duration::from_secs(1)).unwrap();
+                let inputs = vec![(input.as_bytes(), "file:bench.owl".to_string())];
+                load(inputs, &prefixes, &options, &mut sink).unwrap();
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, parse, insert, batch_size);
+criterion_main!(benches);
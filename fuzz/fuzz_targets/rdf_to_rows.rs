@@ -0,0 +1,15 @@
+//! Feed arbitrary input to `rdf_to_rows`, and `thicken` what it returns,
+//! looking for panics and hangs without a database.
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use rdftab::{default_prefixes, rdf_to_rows, thicken};
+
+fuzz_target!(|data: &[u8]| {
+    // rdf_to_rows takes a string, so anything that isn't UTF-8 can't get in.
+    if let Ok(input) = std::str::from_utf8(data) {
+        if let Ok(rows) = rdf_to_rows(input, &default_prefixes()) {
+            thicken(rows);
+        }
+    }
+});
@@ -0,0 +1,8 @@
+#!/bin/sh
+# Seed the corpus with the RDFXML fixtures from test/, then fuzz rdf_to_rows.
+# Any arguments are passed on to `cargo fuzz run`, e.g. `./fuzz/run.sh -- -max_total_time=60`.
+set -e
+cd "$(dirname "$0")/.."
+mkdir -p fuzz/corpus/rdf_to_rows
+cp test/*.owl fuzz/corpus/rdf_to_rows/
+exec cargo +nightly fuzz run rdf_to_rows "$@"
@@ -0,0 +1,2625 @@
+//! Read RDFXML into a SQLite `statements` table.
+//!
+//! The `rdftab` binary is a thin command-line wrapper around these functions.
+use std::cell::Cell;
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::io::{BufRead, IsTerminal, Read, Write};
+use std::path::Path;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime, Timelike};
+use flate2::read::MultiGzDecoder;
+use log::{debug, info, log_enabled, Level};
+use oxiri::Iri;
+use rio_xml::{RdfXmlFormatter, RdfXmlParser, RdfXmlError};
+use rio_api::formatter::TriplesFormatter;
+use rio_api::parser::TriplesParser;
+use rio_api::model::*;
+
+use serde_json::{Map as SerdeMap, Value as SerdeValue};
+
+use rusqlite::{params, Connection, DatabaseName, ErrorCode, OpenFlags, Result};
+
+/// Log a warning, or return it as an error if `strict` is true.
+fn warn(strict: bool, message: &str) -> Result<(), Box<dyn Error>> {
+    if strict {
+        return Err(RdftabError::Parse(message.to_string()).into());
+    }
+    log::warn!("{}", message);
+    Ok(())
+}
+
+/// How often `Progress` reports, in triples.
+const PROGRESS_INTERVAL: usize = 100_000;
+
+/// Report a running count of parsed triples when info messages are logged.
+/// On a terminal each report replaces the last; otherwise each is a line of its own.
+struct Progress {
+    next: usize,
+    terminal: bool,
+    printed: bool,
+}
+
+impl Progress {
+    fn new() -> Progress {
+        Progress { next: PROGRESS_INTERVAL, terminal: io::stderr().is_terminal(), printed: false }
+    }
+
+    fn update(&mut self, parsed: usize) {
+        if parsed < self.next {
+            return;
+        }
+        while self.next <= parsed {
+            self.next += PROGRESS_INTERVAL;
+        }
+        if !log_enabled!(Level::Info) {
+            return;
+        }
+        if self.terminal {
+            // This bypasses the logger: a log record always ends its line, so it can't be overwritten in place.
+            eprint!("\rParsed {} triples", parsed);
+            self.printed = true;
+        } else {
+            info!("Parsed {} triples", parsed);
+        }
+    }
+}
+
+impl Drop for Progress {
+    /// End the terminal line, so that what comes next starts on a line of its own.
+    fn drop(&mut self) {
+        if self.printed {
+            eprintln!();
+        }
+    }
+}
+
+/// The kinds of failure that `rdftab` reports with their own exit codes.
+#[derive(Debug)]
+pub enum RdftabError {
+    /// The arguments don't make sense.
+    Usage(String),
+    /// An input file or database does not exist.
+    NotFound(String),
+    /// The input is not valid RDFXML, or has a problem that `--strict` makes an error.
+    Parse(String),
+    /// SQLite failed.
+    Database(rusqlite::Error),
+    /// Another error, with a message saying what we were doing; it keeps the exit code of the error.
+    Context(String, Box<dyn Error>),
+}
+
+impl RdftabError {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            RdftabError::Usage(_) => 2,
+            RdftabError::NotFound(_) => 3,
+            RdftabError::Parse(_) => 4,
+            RdftabError::Database(_) => 5,
+            RdftabError::Context(_, err) => exit_code(err.as_ref()),
+        }
+    }
+}
+
+impl fmt::Display for RdftabError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RdftabError::Usage(message) | RdftabError::NotFound(message) | RdftabError::Parse(message) => {
+                write!(f, "{}", message)
+            },
+            RdftabError::Database(err) => write!(f, "database error: {}", err),
+            RdftabError::Context(message, err) => write!(f, "{}: {}", message, err),
+        }
+    }
+}
+
+impl Error for RdftabError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            RdftabError::Database(err) => Some(err),
+            RdftabError::Context(_, err) => Some(err.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<rusqlite::Error> for RdftabError {
+    fn from(err: rusqlite::Error) -> RdftabError {
+        RdftabError::Database(err)
+    }
+}
+
+/// The exit code for an error: 2 to 5 for the kinds of `RdftabError` (SQLite errors count as
+/// `Database` and missing files as `NotFound` even when they aren't wrapped),
+/// the code of its source for any other error that has one, and 1 for anything else.
+pub fn exit_code(err: &(dyn Error + 'static)) -> i32 {
+    if let Some(err) = err.downcast_ref::<RdftabError>() {
+        err.exit_code()
+    } else if err.is::<rusqlite::Error>() {
+        5
+    } else if let Some(err) = err.downcast_ref::<io::Error>() {
+        if err.kind() == io::ErrorKind::NotFound { 3 } else { 1 }
+    } else if let Some(source) = err.source() {
+        exit_code(source)
+    } else {
+        1
+    }
+}
+
+/// Open a database file that must already exist.
+fn open_existing(db: &str, flags: OpenFlags) -> Result<Connection, Box<dyn Error>> {
+    if db != ":memory:" && !Path::new(db).exists() {
+        return Err(RdftabError::NotFound(format!("database {} does not exist", db)).into());
+    }
+    Ok(Connection::open_with_flags(db, flags)?)
+}
+
+#[derive(Clone, Debug)]
+pub struct Prefix {
+    pub prefix: String,
+    pub base: String
+}
+
+pub fn get_prefixes(conn: &mut Connection) -> Result<Vec<Prefix>> {
+    // A NULL prefix is the same as the empty prefix, as in `@prefix : <...> .`
+    // Break ties between bases of the same length by prefix, so `shorten` always picks the same one.
+    let mut stmt = conn.prepare("SELECT coalesce(prefix, ''), base FROM prefix
+      ORDER BY length(base) DESC, coalesce(prefix, '')")?;
+    let mut rows = stmt.query(params![])?;
+    let mut prefixes = Vec::new();
+    while let Some(row) = rows.next()? {
+        prefixes.push(Prefix { prefix: row.get(0)?, base: row.get(1)? });
+    }
+    Ok(prefixes)
+}
+
+/// The error for a file that can't be opened: `NotFound` if it doesn't exist, so it exits with code 3.
+fn open_error(path: &str, err: io::Error) -> Box<dyn Error> {
+    match err.kind() {
+        io::ErrorKind::NotFound => RdftabError::NotFound(format!("Cannot open {}: {}", path, err)).into(),
+        _ => err.into(),
+    }
+}
+
+/// Read prefix/base pairs from a Turtle-style `@prefix` file,
+/// or from a CSV/TSV file with `prefix` and `base` columns.
+pub fn read_prefix_file(path: &str) -> Result<Vec<Prefix>, Box<dyn Error>> {
+    let content = fs::read_to_string(path).map_err(|err| open_error(path, err))?;
+    if path.ends_with(".csv") || path.ends_with(".tsv") {
+        return read_prefix_table(path, &content, if path.ends_with(".tsv") { b'\t' } else { b',' });
+    }
+    let mut prefixes = Vec::new();
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        // @prefix ex: <http://example.com/> .
+        let mut words = line.split_whitespace();
+        let parsed = match (words.next(), words.next(), words.next()) {
+            (Some(keyword), Some(prefix), Some(base))
+                if (keyword == "@prefix" || keyword.eq_ignore_ascii_case("prefix"))
+                    && prefix.ends_with(':')
+                    && base.starts_with('<') && base.ends_with('>') =>
+                Some((&prefix[..prefix.len() - 1], &base[1..base.len() - 1])),
+            _ => None,
+        };
+        match parsed {
+            Some((prefix, base)) => prefixes.push(Prefix { prefix: prefix.to_string(), base: base.to_string() }),
+            None => return Err(RdftabError::Parse(format!("{}:{}: cannot parse prefix from '{}'", path, i + 1, line)).into()),
+        }
+    }
+    Ok(prefixes)
+}
+
+/// Read the rows of a CSV or TSV prefix file, skipping a `prefix,base` header and `#` comments.
+fn read_prefix_table(path: &str, content: &str, delimiter: u8) -> Result<Vec<Prefix>, Box<dyn Error>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .has_headers(false)
+        .flexible(true)
+        .comment(Some(b'#'))
+        .trim(csv::Trim::All)
+        .from_reader(content.as_bytes());
+    let mut prefixes = Vec::new();
+    for record in reader.records() {
+        let record = record.map_err(|err| RdftabError::Parse(format!("{}: {}", path, err)))?;
+        let line = record.position().map_or(0, |position| position.line());
+        match (record.get(0), record.get(1)) {
+            (Some("prefix"), Some("base")) => continue,
+            (Some(prefix), Some(base)) => prefixes.push(Prefix { prefix: prefix.to_string(), base: base.to_string() }),
+            _ => return Err(RdftabError::Parse(format!(
+                "{}:{}: cannot parse prefix from '{}'", path, line, record.iter().collect::<Vec<_>>().join(",")
+            )).into()),
+        }
+    }
+    Ok(prefixes)
+}
+
+/// Read prefixes from a JSON object mapping each prefix to its base,
+/// such as a JSON-LD context, with or without an enclosing `@context` key.
+/// Keys starting with `@` and values that aren't strings are not prefixes.
+pub fn read_prefix_json(path: &str) -> Result<Vec<Prefix>, Box<dyn Error>> {
+    let content = fs::read_to_string(path).map_err(|err| open_error(path, err))?;
+    let json: SerdeValue = serde_json::from_str(&content)
+        .map_err(|err| RdftabError::Parse(format!("{}: {}", path, err)))?;
+    let context = json.get("@context").unwrap_or(&json);
+    let map = context.as_object()
+        .ok_or_else(|| RdftabError::Parse(format!("{}: expected a JSON object of prefixes", path)))?;
+    Ok(map.iter()
+        .filter(|(prefix, _)| !prefix.starts_with('@'))
+        .filter_map(|(prefix, base)| base.as_str().map(|base| Prefix { prefix: prefix.to_string(), base: base.to_string() }))
+        .collect())
+}
+
+/// Merge prefixes into the `prefix` table, creating it if needed.
+/// The new prefixes replace existing rows with the same prefix.
+pub fn store_prefixes(conn: &mut Connection, prefixes: &[Prefix]) -> Result<()> {
+    let tx = conn.transaction()?;
+    create_prefix_table(&tx)?;
+    {
+        let mut stmt = tx.prepare("INSERT OR REPLACE INTO prefix VALUES (?1, ?2)")?;
+        for prefix in prefixes {
+            stmt.execute(params![prefix.prefix, prefix.base])?;
+        }
+    }
+    tx.commit()
+}
+
+/// The standard prefixes used when the `prefix` table is empty.
+pub fn default_prefixes() -> Vec<Prefix> {
+    vec![
+        ("rdf", "http://www.w3.org/1999/02/22-rdf-syntax-ns#"),
+        ("rdfs", "http://www.w3.org/2000/01/rdf-schema#"),
+        ("owl", "http://www.w3.org/2002/07/owl#"),
+        ("xsd", "http://www.w3.org/2001/XMLSchema#"),
+        ("xml", "http://www.w3.org/XML/1998/namespace"),
+    ].into_iter()
+        .map(|(prefix, base)| Prefix { prefix: prefix.to_string(), base: base.to_string() })
+        .collect()
+}
+
+pub fn table_exists(conn: &Connection, table: &str) -> Result<bool> {
+    let count: i64 = conn.query_row(
+        "SELECT count(*) FROM sqlite_master WHERE type = 'table' AND name = ?1",
+        params![table],
+        |row| row.get(0),
+    )?;
+    Ok(count > 0)
+}
+
+pub fn create_prefix_table(conn: &Connection) -> Result<()> {
+    conn.execute("CREATE TABLE IF NOT EXISTS prefix (
+      prefix TEXT PRIMARY KEY,
+      base TEXT NOT NULL
+    )", params![])?;
+    Ok(())
+}
+
+pub fn create_statements_table(conn: &Connection) -> Result<()> {
+    conn.execute("CREATE TABLE IF NOT EXISTS statements (
+      stanza TEXT,
+      subject TEXT,
+      predicate TEXT,
+      object TEXT,
+      value TEXT,
+      datatype TEXT,
+      language TEXT
+    )", params![])?;
+    Ok(())
+}
+
+/// Find the start tag of the root element, skipping the XML declaration,
+/// comments, and any DOCTYPE. Return None if the text ends before the tag does.
+fn root_start_tag(text: &str) -> Option<&str> {
+    let mut rest = text;
+    loop {
+        let start = rest.find('<')?;
+        rest = &rest[start..];
+        let end = if rest.starts_with("<?") {
+            rest.find("?>")? + 2
+        } else if rest.starts_with("<!--") {
+            rest.find("-->")? + 3
+        } else if rest.starts_with("<!") {
+            match (rest.find('['), rest.find('>')) {
+                (Some(open), Some(close)) if open < close => rest.find("]>")? + 2,
+                (_, close) => close? + 1,
+            }
+        } else {
+            return Some(&rest[..rest.find('>')? + 1]);
+        };
+        rest = &rest[end..];
+    }
+}
+
+/// Read the start of an RDFXML document up to the end of the root element's start tag.
+/// Return the bytes that were read, and the `xmlns:` declarations found on the root element.
+/// A default `xmlns` declaration becomes the empty prefix.
+pub fn read_namespaces<R: BufRead>(reader: &mut R) -> io::Result<(Vec<u8>, Vec<Prefix>)> {
+    let mut head = Vec::new();
+    let mut prefixes = Vec::new();
+    while reader.read_until(b'>', &mut head)? > 0 {
+        let text = String::from_utf8_lossy(&head);
+        if let Some(tag) = root_start_tag(&text) {
+            let mut rest = tag;
+            while let Some(start) = rest.find("xmlns") {
+                rest = &rest[start + 5..];
+                let eq = match rest.find('=') {
+                    Some(eq) => eq,
+                    None => break,
+                };
+                let prefix = match rest[..eq].trim() {
+                    "" => "",
+                    name if name.starts_with(':') => &name[1..],
+                    _ => continue,
+                };
+                let value = rest[eq + 1..].trim_start();
+                let quote = match value.chars().next() {
+                    Some(q) if q == '"' || q == '\'' => q,
+                    _ => continue,
+                };
+                let base = match value[1..].find(quote) {
+                    Some(end) => &value[1..end + 1],
+                    None => break,
+                };
+                // Skip entity references such as xmlns:obo="&obo;".
+                if !base.contains('&') {
+                    prefixes.push(Prefix { prefix: prefix.to_string(), base: base.to_string() });
+                }
+            }
+            break;
+        }
+    }
+    Ok((head, prefixes))
+}
+
+/// If the input starts with the gzip magic bytes, wrap it in a decoder.
+pub fn maybe_gunzip<'a, R: BufRead + 'a>(mut input: R) -> io::Result<Box<dyn BufRead + 'a>> {
+    if input.fill_buf()?.starts_with(&[0x1f, 0x8b]) {
+        Ok(Box::new(io::BufReader::new(MultiGzDecoder::new(input))))
+    } else {
+        Ok(Box::new(input))
+    }
+}
+
+/// Skip a UTF-8 byte order mark at the start of `input`, which the XML parser rejects.
+pub fn skip_bom<R: BufRead>(input: &mut R) -> io::Result<()> {
+    if input.fill_buf()?.starts_with(&[0xef, 0xbb, 0xbf]) {
+        input.consume(3);
+    }
+    Ok(())
+}
+
+/// Decompress `input` if it's gzipped, and skip any byte order mark.
+fn open_reader<'a, R: BufRead + 'a>(input: R) -> io::Result<Box<dyn BufRead + 'a>> {
+    let mut reader = maybe_gunzip(input)?;
+    skip_bom(&mut reader)?;
+    Ok(reader)
+}
+
+/// Wrap a reader to count the lines consumed by the parser,
+/// so that parse errors can say where they happened.
+struct LineCounter<R> {
+    inner: R,
+    line: Rc<Cell<usize>>,
+}
+
+impl<R: BufRead> Read for LineCounter<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        let lines = buf[..n].iter().filter(|&&b| b == b'\n').count();
+        self.line.set(self.line.get() + lines);
+        Ok(n)
+    }
+}
+
+impl<R: BufRead> BufRead for LineCounter<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        if let Ok(buf) = self.inner.fill_buf() {
+            let lines = buf[..amt.min(buf.len())].iter().filter(|&&b| b == b'\n').count();
+            self.line.set(self.line.get() + lines);
+        }
+        self.inner.consume(amt);
+    }
+}
+
+/// Shorten an IRI to a prefixed name, or return it unchanged if no prefix matches.
+/// IRIs are stored without angle brackets; the Turtle export adds them.
+pub fn shorten(prefixes: &[Prefix], iri: &str) -> String {
+    for prefix in prefixes {
+        if iri.starts_with(&prefix.base) {
+            // Only use the prefix when what remains is a local name,
+            // not a path into a nested namespace.
+            let local = &iri[prefix.base.len()..];
+            if !local.contains('/') && !local.contains('#') {
+                return format!("{}:{}", prefix.prefix, local);
+            }
+        }
+    }
+    iri.to_string()
+}
+
+/// The inverse of `shorten`: expand a CURIE with a known prefix, or strip the brackets from an `<IRI>`.
+/// Anything else, such as an IRI, a blank node, or a literal value, is returned unchanged.
+pub fn expand(prefixes: &[Prefix], curie: &str) -> String {
+    full_iri(prefixes, curie).unwrap_or_else(|| curie.to_string())
+}
+
+/// Return the full IRI for a CURIE with a known prefix, an IRI,
+/// or an `<IRI>` as stored by older versions of `rdftab`.
+/// Return None for a term that has no colon, and so cannot be either.
+fn full_iri(prefixes: &[Prefix], term: &str) -> Option<String> {
+    if term.starts_with('<') && term.ends_with('>') {
+        return Some(term[1..term.len() - 1].to_string());
+    }
+    let colon = term.find(':')?;
+    let (prefix, local) = (&term[..colon], &term[colon + 1..]);
+    match prefixes.iter().find(|p| p.prefix == prefix) {
+        Some(p) => Some(format!("{}{}", p.base, local)),
+        None => Some(term.to_string()),
+    }
+}
+
+/// Re-apply the `prefix` table to the stored terms: expand each one with the current prefixes,
+/// and shorten it again, with one UPDATE per column.
+/// A prefixed name is expanded with the current base for its prefix, whatever it was loaded with.
+pub fn reshorten(db: &str) -> Result<(), Box<dyn Error>> {
+    let mut conn = open_existing(db, OpenFlags::SQLITE_OPEN_READ_WRITE)?;
+    let prefixes = get_prefixes(&mut conn)?;
+    let tx = conn.transaction()?;
+    create_term_map(&tx, "statements", &TERM_COLUMNS, |term| match full_iri(&prefixes, term) {
+        Some(iri) => shorten(&prefixes, &iri),
+        None => term.to_string(),
+    })?;
+    let mut updated = 0;
+    for column in &TERM_COLUMNS {
+        updated += tx.execute(&format!(
+            "UPDATE statements SET {0} = (SELECT new FROM temp.term_map WHERE old = statements.{0})
+             WHERE {0} IN (SELECT old FROM temp.term_map)",
+            column
+        ), params![])?;
+    }
+    tx.execute("DROP TABLE temp.term_map", params![])?;
+    tx.commit()?;
+    info!("Updated {} values", updated);
+    Ok(())
+}
+
+/// Print the `prefix` table of a database as Turtle `@prefix` lines, sorted by prefix.
+pub fn prefixes(db: &str) -> Result<(), Box<dyn Error>> {
+    let mut conn = open_existing(db, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let mut prefixes = get_prefixes(&mut conn)?;
+    prefixes.sort_by(|a, b| a.prefix.cmp(&b.prefix).then_with(|| a.base.cmp(&b.base)));
+    let stdout = io::stdout();
+    let mut out = io::BufWriter::new(stdout.lock());
+    for prefix in &prefixes {
+        writeln!(out, "@prefix {}: <{}> .", prefix.prefix, prefix.base)?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// Delete the statements in the named `graph` of a database, in one transaction,
+/// or the statements with no graph if `graph` is `None`. The prefix table is left alone.
+pub fn drop_graph(db: &str, graph: Option<&str>) -> Result<(), Box<dyn Error>> {
+    let mut conn = open_existing(db, OpenFlags::SQLITE_OPEN_READ_WRITE)?;
+    if !table_exists(&conn, "statements")? {
+        return Err(RdftabError::NotFound(format!("{} has no statements table", db)).into());
+    }
+    if !column_exists(&conn, "statements", "graph")? {
+        return Err(RdftabError::NotFound(format!("{} has no graph column: load with --graph first", db)).into());
+    }
+    conn.busy_timeout(Duration::from_millis(DEFAULT_BUSY_TIMEOUT_MS))?;
+    let tx = conn.transaction()?;
+    let deleted = match graph {
+        Some(graph) => tx.execute("DELETE FROM statements WHERE graph = ?", params![graph])?,
+        None => tx.execute("DELETE FROM statements WHERE graph IS NULL", params![])?,
+    };
+    tx.commit()?;
+    info!("Deleted {} statements from {}", deleted, graph.unwrap_or("the default graph"));
+    Ok(())
+}
+
+/// The statements columns that hold IRIs, CURIEs, or blank nodes, rather than literal values.
+const TERM_COLUMNS: [&str; 5] = ["stanza", "subject", "predicate", "object", "datatype"];
+
+/// Fill a temporary `term_map(old, new)` table with each term in `columns` of `table`
+/// that `translate` changes, so that one statement per column can apply them all.
+/// The terms inside a JSON object stored with `--thick` are translated too.
+/// Return the number of terms that change.
+fn create_term_map(
+    conn: &Connection,
+    table: &str,
+    columns: &[&str],
+    translate: impl Fn(&str) -> String,
+) -> Result<usize, Box<dyn Error>> {
+    conn.execute_batch("DROP TABLE IF EXISTS temp.term_map;
+      CREATE TEMP TABLE term_map (old TEXT PRIMARY KEY, new TEXT NOT NULL);")?;
+    let terms = columns.iter()
+        .map(|column| format!("SELECT {0} FROM {1} WHERE {0} IS NOT NULL", column, table))
+        .collect::<Vec<_>>()
+        .join(" UNION ");
+    let mut changes = Vec::new();
+    {
+        let mut stmt = conn.prepare(&terms)?;
+        let mut rows = stmt.query(params![])?;
+        while let Some(row) = rows.next()? {
+            let term: String = row.get(0)?;
+            let json = if term.starts_with('{') { serde_json::from_str(&term).ok() } else { None };
+            let new = match json {
+                Some(json) => translate_json(&json, &translate).to_string(),
+                None => translate(&term),
+            };
+            if new != term {
+                changes.push((term, new));
+            }
+        }
+    }
+    let mut stmt = conn.prepare("INSERT INTO temp.term_map VALUES (?, ?)")?;
+    for (old, new) in &changes {
+        stmt.execute(params![old, new])?;
+    }
+    Ok(changes.len())
+}
+
+/// Translate the predicates, objects, and datatypes in JSON from `thicken`, keeping the literal values.
+fn translate_json(json: &SerdeValue, translate: &impl Fn(&str) -> String) -> SerdeValue {
+    let predicates = match json.as_object() {
+        Some(predicates) => predicates,
+        None => return json.clone(),
+    };
+    let mut translated = SerdeMap::new();
+    for (predicate, cells) in predicates.iter() {
+        let cells = cells.as_array().into_iter().flatten()
+            .map(|cell| match cell.as_object() {
+                Some(cell) => SerdeValue::Object(cell.iter()
+                    .map(|(key, value)| {
+                        let value = match (key.as_str(), value) {
+                            ("object", SerdeValue::String(term)) | ("datatype", SerdeValue::String(term)) =>
+                                SerdeValue::String(translate(term)),
+                            ("object", nested) => translate_json(nested, translate),
+                            _ => value.clone(),
+                        };
+                        (key.clone(), value)
+                    })
+                    .collect()),
+                None => cell.clone(),
+            })
+            .collect();
+        translated.insert(translate(predicate), SerdeValue::Array(cells));
+    }
+    SerdeValue::Object(translated)
+}
+
+/// An SQL expression for `column` of the table `alias`, translated by the `term_map` table.
+fn mapped(alias: &str, column: &str) -> String {
+    format!("coalesce((SELECT new FROM temp.term_map WHERE old = {0}.{1}), {0}.{1})", alias, column)
+}
+
+/// The optional statements columns that `merge` carries over, with their types.
+const EXTRA_COLUMNS: [(&str, &str); 3] = [("kind", "TEXT"), ("seq", "INTEGER"), ("graph", "TEXT")];
+
+/// Copy the statements and prefixes of the database `source` into the database `target`.
+/// Each term is expanded with the `source` prefixes and shortened with the `target` prefixes,
+/// so a prefix that `target` already has with a different base keeps its `target` base
+/// without changing what the copied statements mean.
+/// Blank nodes from `source` get a new label prefix so they can't collide with those in `target`,
+/// and statements without blank nodes that are already in `target` (in the same graph),
+/// or that come earlier in `source`, are skipped.
+/// The `kind`, `seq`, and `graph` columns are copied too, adding them to `target` if needed,
+/// and `seq` numbers carry on from the highest in `target`.
+pub fn merge(source: &str, target: &str, strict: bool) -> Result<(), Box<dyn Error>> {
+    let (source_prefixes, source_extras) = {
+        let mut from = open_existing(source, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+        if !table_exists(&from, "statements")? {
+            return Err(RdftabError::NotFound(format!("{} has no statements table", source)).into());
+        }
+        let mut extras = Vec::new();
+        for (column, _) in &EXTRA_COLUMNS {
+            if column_exists(&from, "statements", column)? {
+                extras.push(*column);
+            }
+        }
+        let prefixes = if table_exists(&from, "prefix")? { get_prefixes(&mut from)? } else { Vec::new() };
+        (prefixes, extras)
+    };
+
+    let mut conn = Connection::open(target)?;
+    conn.busy_timeout(Duration::from_millis(DEFAULT_BUSY_TIMEOUT_MS))?;
+    create_prefix_table(&conn)?;
+    create_statements_table(&conn)?;
+    let target_prefixes = get_prefixes(&mut conn)?;
+    let mut new_prefixes = Vec::new();
+    for prefix in &source_prefixes {
+        match target_prefixes.iter().find(|p| p.prefix == prefix.prefix) {
+            Some(existing) if existing.base != prefix.base => warn(strict, &format!(
+                "prefix '{}' is <{}> in {} but <{}> in {}; keeping <{}> and rewriting the copied statements",
+                prefix.prefix, prefix.base, source, existing.base, target, existing.base
+            ))?,
+            Some(_) => (),
+            None => new_prefixes.push(prefix.clone()),
+        }
+    }
+    store_prefixes(&mut conn, &new_prefixes)?;
+    let target_prefixes = get_prefixes(&mut conn)?;
+
+    // ATTACH is not allowed inside a transaction.
+    conn.execute("ATTACH DATABASE ?1 AS source", params![source])?;
+    let tx = conn.transaction()?;
+    let mut columns: Vec<&str> = COLUMNS.to_vec();
+    for (column, kind) in &EXTRA_COLUMNS {
+        if source_extras.contains(column) && !column_exists(&tx, "statements", column)? {
+            tx.execute(&format!("ALTER TABLE main.statements ADD COLUMN {} {}", column, kind), params![])?;
+        }
+        if column_exists(&tx, "statements", column)? {
+            columns.push(column);
+        }
+    }
+    let mut term_columns = TERM_COLUMNS.to_vec();
+    if source_extras.contains(&"graph") {
+        term_columns.push("graph");
+    }
+    create_term_map(&tx, "source.statements", &term_columns, |term| {
+        shorten(&target_prefixes, &expand(&source_prefixes, term))
+    })?;
+
+    // Find a blank node label prefix that isn't used in the target yet.
+    let mut n = 1;
+    let label = loop {
+        let label = format!("_:m{}-", n);
+        let used: i64 = tx.query_row(
+            "SELECT count(*) FROM (
+               SELECT 1 FROM main.statements
+               WHERE substr(stanza, 1, ?2) = ?1 OR substr(subject, 1, ?2) = ?1 OR substr(object, 1, ?2) = ?1
+               LIMIT 1
+             )",
+            params![label, label.len() as i64],
+            |row| row.get(0),
+        )?;
+        if used == 0 {
+            break label;
+        }
+        n += 1;
+    };
+    let seq_offset: i64 = if columns.contains(&"seq") {
+        tx.query_row("SELECT coalesce(max(seq), 0) FROM main.statements", params![], |row| row.get(0))?
+    } else {
+        0
+    };
+    let copied: Vec<String> = columns.iter()
+        .map(|column| {
+            let value = match *column {
+                _ if !COLUMNS.contains(column) && !source_extras.contains(column) => String::from("NULL"),
+                "stanza" | "subject" | "object" => format!(
+                    "CASE WHEN substr(s.{0}, 1, 2) = '_:' THEN '{1}' || substr(s.{0}, 3) ELSE {2} END",
+                    column, label, mapped("s", column)
+                ),
+                "predicate" | "datatype" | "graph" => mapped("s", column),
+                "seq" => format!("s.seq + {}", seq_offset),
+                _ => format!("s.{}", column),
+            };
+            format!("{} AS {}", value, column)
+        })
+        .collect();
+    let mut same = vec!["subject", "predicate", "object", "value", "datatype", "language"];
+    if columns.contains(&"graph") {
+        same.push("graph");
+    }
+    // Only the first of the statements in `source` that are the same is copied.
+    // They're grouped before their terms are translated, which gives the same groups.
+    let source_same: Vec<&str> = same.iter().cloned()
+        .filter(|column| COLUMNS.contains(column) || source_extras.contains(column))
+        .collect();
+    let is_same: Vec<String> = same.iter().map(|column| format!("t.{0} IS s.{0}", column)).collect();
+    // An index for finding statements that are already in the target, dropped again when we're done.
+    tx.execute_batch(&format!(
+        "DROP INDEX IF EXISTS main.rdftab_merge;
+         CREATE INDEX main.rdftab_merge ON statements ({});",
+        same.join(", ")
+    ))?;
+    let total: i64 = tx.query_row("SELECT count(*) FROM source.statements", params![], |row| row.get(0))?;
+    let added = tx.execute(format!(
+        "INSERT INTO main.statements ({0})
+         SELECT {0} FROM (
+           SELECT {1} FROM source.statements AS s
+           WHERE substr(s.subject, 1, 2) = '_:'
+              OR substr(s.object, 1, 2) = '_:'
+              OR s.rowid IN (SELECT min(rowid) FROM source.statements GROUP BY {2})
+         ) AS s
+         WHERE substr(s.subject, 1, 2) = '_:'
+            OR substr(s.object, 1, 2) = '_:'
+            OR NOT EXISTS (SELECT 1 FROM main.statements AS t WHERE {3})",
+        columns.join(", "), copied.join(", "), source_same.join(", "), is_same.join(" AND ")
+    ).as_str(), params![])?;
+    tx.execute_batch("DROP INDEX main.rdftab_merge; DROP TABLE temp.term_map;")?;
+    tx.commit()?;
+    info!("Added {} statements from {}, skipped {} duplicates", added, source, total as usize - added);
+    Ok(())
+}
+
+/// Rows per batch for `insert_rows`, which splits a batch into INSERT statements
+/// of at most `MAX_PARAMETERS`, so seven-column rows go in about 140 at a time.
+/// The `batch_size` benchmark compares this with smaller and larger batches.
+pub const DEFAULT_BATCH_SIZE: usize = 500;
+
+/// SQLite's default limit on the parameters in one statement.
+pub const MAX_PARAMETERS: usize = 999;
+
+/// How long to wait for another process to release the database.
+pub const DEFAULT_BUSY_TIMEOUT_MS: u64 = 5000;
+
+#[derive(Clone, Debug)]
+pub struct Options {
+    /// The syntax of the inputs: "rdfxml", or "csv" or "tsv" for statement rows.
+    pub format: String,
+    /// RDFXML files to read, in order, instead of STDIN.
+    pub inputs: Vec<String>,
+    /// A prefix file to load into the `prefix` table before parsing.
+    pub prefixes: Option<String>,
+    /// A JSON file of prefixes to load into the `prefix` table before parsing, after `prefixes`.
+    pub prefixes_json: Option<String>,
+    /// Do not fill an empty `prefix` table from the document's `xmlns:` declarations.
+    pub no_auto_prefix: bool,
+    /// Do not fill an empty `prefix` table with `default_prefixes()`.
+    pub no_default_prefixes: bool,
+    /// The number of rows to insert with each INSERT statement.
+    pub batch_size: usize,
+    /// Skip statements identical to one already inserted during this run.
+    pub dedup: bool,
+    /// Drop any existing statements table instead of appending to it.
+    pub replace: bool,
+    /// Parse and count statements, but do not write to the database.
+    pub dry_run: bool,
+    /// A string to put between `_:` and the parser's blank node labels.
+    pub bnode_prefix: String,
+    /// Trade durability for speed with SQLite pragmas suited to one-shot bulk loads.
+    pub fast: bool,
+    /// Milliseconds to keep retrying while the database is locked by another process.
+    pub busy_timeout_ms: u64,
+    /// A file to copy the finished database to, e.g. when loading into `:memory:`.
+    pub save: Option<String>,
+    /// Store typed literals in the canonical lexical form of their datatype.
+    pub canonicalize_literals: bool,
+    /// Number the statements in the order they were parsed, in a `seq` column.
+    pub preserve_order: bool,
+    /// Also print each statement to STDOUT as a line of JSON.
+    pub json: bool,
+    /// Stop after this many stanzas.
+    pub limit: Option<usize>,
+    /// Only keep statements about subjects that start with this prefixed name or IRI.
+    pub subject_prefix: Option<String>,
+    /// Skip statements with these predicates, given as prefixed names or IRIs.
+    pub exclude_predicates: Vec<String>,
+    /// Do not record this import in the import_metadata table.
+    pub no_metadata: bool,
+    /// Check that every subject, predicate, and object IRI is well-formed.
+    pub validate_iris: bool,
+    /// Resolve relative IRIs against this base, instead of the `file:` IRI of each input.
+    pub base: Option<String>,
+    /// Remove leading and trailing whitespace from literal values, except XML literals.
+    pub trim_literals: bool,
+    /// Store language tags in lowercase.
+    pub normalize_lang: bool,
+    /// Commit after every N rows, instead of once at the end.
+    pub commit_every: Option<usize>,
+    /// Say whether each object is an "iri", a "bnode", or a "literal", in a `kind` column.
+    pub emit_kind: bool,
+    /// Store full IRIs instead of prefixed names, leaving the prefix table as it is.
+    pub no_shorten: bool,
+    /// Store blank nodes used once in their stanza as JSON in the `object` column (see `thicken`).
+    pub thick: bool,
+    /// Put every statement in this named graph, in a `graph` column.
+    pub graph: Option<String>,
+    /// Write the counts from the summary, and more, to this file as a JSON object.
+    pub stats_json: Option<String>,
+    /// Stop with an error instead of logging a warning.
+    pub strict: bool,
+    /// What this load has counted so far, shared by copies of these options.
+    pub counters: Arc<Counters>,
+}
+
+/// Counts kept during a load, for its summary and for `stats_json`.
+#[derive(Debug, Default)]
+pub struct Counters {
+    /// Warnings logged by `Options::warn`.
+    pub warnings: AtomicUsize,
+    /// Statements skipped because of `exclude_predicates`.
+    pub excluded: AtomicUsize,
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            format: String::from("rdfxml"),
+            inputs: Vec::new(),
+            prefixes: None,
+            prefixes_json: None,
+            no_auto_prefix: false,
+            no_default_prefixes: false,
+            batch_size: DEFAULT_BATCH_SIZE,
+            dedup: false,
+            replace: false,
+            dry_run: false,
+            bnode_prefix: String::new(),
+            fast: false,
+            busy_timeout_ms: DEFAULT_BUSY_TIMEOUT_MS,
+            save: None,
+            canonicalize_literals: false,
+            preserve_order: false,
+            json: false,
+            limit: None,
+            subject_prefix: None,
+            exclude_predicates: Vec::new(),
+            no_metadata: false,
+            validate_iris: false,
+            base: None,
+            trim_literals: false,
+            normalize_lang: false,
+            commit_every: None,
+            emit_kind: false,
+            no_shorten: false,
+            thick: false,
+            graph: None,
+            stats_json: None,
+            strict: false,
+            counters: Arc::new(Counters::default()),
+        }
+    }
+}
+
+impl Options {
+    /// The full IRIs of `exclude_predicates`, which may be prefixed names.
+    fn excluded_predicates(&self, prefixes: &[Prefix]) -> BTreeSet<String> {
+        self.exclude_predicates.iter().map(|predicate| expand(prefixes, predicate)).collect()
+    }
+
+    /// Log a warning and count it, or return it as an error in strict mode.
+    pub fn warn(&self, message: &str) -> Result<(), Box<dyn Error>> {
+        warn(self.strict, message)?;
+        self.counters.warnings.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+fn is_busy(err: &rusqlite::Error) -> bool {
+    match err {
+        rusqlite::Error::SqliteFailure(e, _) => {
+            e.code == ErrorCode::DatabaseBusy || e.code == ErrorCode::DatabaseLocked
+        },
+        _ => false,
+    }
+}
+
+/// Call `f` until it succeeds or fails for a reason other than a busy or locked database,
+/// backing off exponentially for at most `timeout`.
+///
+/// SQLite's own busy handler covers most waits, but it gives up at once
+/// when waiting could deadlock, so we need a loop of our own as well.
+fn retry_busy<T>(timeout: Duration, mut f: impl FnMut() -> Result<T>) -> Result<T, Box<dyn Error>> {
+    let start = Instant::now();
+    let mut wait = Duration::from_millis(10);
+    loop {
+        match f() {
+            Err(err) if is_busy(&err) => {
+                let elapsed = start.elapsed();
+                if elapsed >= timeout {
+                    let message = format!("Gave up after waiting {} ms for the database", timeout.as_millis());
+                    return Err(RdftabError::Context(message, Box::new(err)).into());
+                }
+                debug!("Database is busy, retrying in {} ms", wait.as_millis());
+                thread::sleep(wait.min(timeout - elapsed));
+                wait *= 2;
+            },
+            result => return Ok(result.map_err(RdftabError::Database)?),
+        }
+    }
+}
+
+pub fn column_exists(conn: &Connection, table: &str, column: &str) -> Result<bool> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+    let mut rows = stmt.query(params![])?;
+    while let Some(row) = rows.next()? {
+        if row.get::<_, String>(1)? == column {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Insert rows into the statements table using multi-row INSERTs,
+/// as few as `MAX_PARAMETERS` allows.
+/// The rows have a cell for each of `columns`, and `seq_offset` is added to any `seq` cell.
+fn insert_rows(
+    conn: &Connection,
+    rows: &[Vec<Option<String>>],
+    columns: &[&str],
+    seq_offset: i64,
+    timeout: Duration,
+) -> Result<(), Box<dyn Error>> {
+    let values: Vec<String> = columns.iter()
+        .map(|column| if *column == "seq" { format!("? + {}", seq_offset) } else { String::from("?") })
+        .collect();
+    for chunk in rows.chunks(MAX_PARAMETERS / columns.len()) {
+        let sql = format!(
+            "INSERT INTO statements ({}) VALUES {}",
+            columns.join(", "),
+            vec![format!("({})", values.join(", ")); chunk.len()].join(", "),
+        );
+        let mut stmt = conn.prepare_cached(&sql)?;
+        if let Err(err) = retry_busy(timeout, || stmt.execute(chunk.iter().flatten())) {
+            let message = format!("Failed to insert a batch of rows starting with {:?}", chunk[0]);
+            return Err(RdftabError::Context(message, err).into());
+        }
+    }
+    Ok(())
+}
+
+/// Somewhere to store statement rows: `load` creates the table,
+/// inserts the rows in batches, and then commits.
+pub trait StatementSink {
+    /// Create the statements table, first dropping any existing one if `options.replace` is true
+    /// (or, with `options.graph`, just deleting that graph's statements),
+    /// with a `kind` column if `options.emit_kind` is true,
+    /// a `seq` column if `options.preserve_order` is true,
+    /// and a `graph` column if `options.graph` is set.
+    fn create_table(&mut self, options: &Options) -> Result<(), Box<dyn Error>>;
+    /// Insert a batch of rows from `parse_stanzas`.
+    fn insert_rows(&mut self, rows: &[Vec<Option<String>>]) -> Result<(), Box<dyn Error>>;
+    /// Make everything inserted so far permanent.
+    fn commit(&mut self) -> Result<(), Box<dyn Error>>;
+}
+
+/// Store statements in a SQLite database, inside a single transaction,
+/// or a new one after every so many rows if `commit_every` is set.
+pub struct SqliteSink {
+    conn: Connection,
+    busy_timeout: Duration,
+    columns: Vec<&'static str>,
+    seq_offset: i64,
+    source: Option<String>,
+    inserted: usize,
+    commit_every: Option<usize>,
+    uncommitted: usize,
+}
+
+impl SqliteSink {
+    /// Start a write transaction, waiting up to `busy_timeout` for other writers to finish.
+    pub fn new(conn: Connection, busy_timeout: Duration) -> Result<SqliteSink, Box<dyn Error>> {
+        conn.busy_timeout(busy_timeout)?;
+        // Take the write lock now, rather than failing to upgrade to it mid-load.
+        retry_busy(busy_timeout, || conn.execute_batch("BEGIN IMMEDIATE"))?;
+        Ok(SqliteSink { conn, busy_timeout, columns: COLUMNS.to_vec(), seq_offset: 0, source: None, inserted: 0, commit_every: None, uncommitted: 0 })
+    }
+
+    /// On commit, add a row to the import_metadata table saying that the statements came from `source`.
+    pub fn record_source(&mut self, source: &str) {
+        self.source = Some(source.to_string());
+    }
+
+    /// Commit and start a new transaction after every `rows` rows,
+    /// so that a failed load keeps what was committed before it failed.
+    pub fn commit_every(&mut self, rows: usize) {
+        self.commit_every = Some(rows);
+    }
+
+    /// Give back the connection, e.g. to save an in-memory database after `commit`.
+    pub fn into_connection(self) -> Connection {
+        self.conn
+    }
+}
+
+impl StatementSink for SqliteSink {
+    fn create_table(&mut self, options: &Options) -> Result<(), Box<dyn Error>> {
+        if options.replace && options.graph.is_none() {
+            self.conn.execute("DROP TABLE IF EXISTS statements", params![])?;
+        }
+        create_statements_table(&self.conn)?;
+        self.columns = row_columns(options);
+        if let Some(ref graph) = options.graph {
+            if !column_exists(&self.conn, "statements", "graph")? {
+                self.conn.execute("ALTER TABLE statements ADD COLUMN graph TEXT", params![])?;
+            }
+            if options.replace {
+                let deleted = self.conn.execute("DELETE FROM statements WHERE graph = ?", params![graph])?;
+                info!("Deleted {} statements from graph {}", deleted, graph);
+            }
+        }
+        if options.emit_kind && !column_exists(&self.conn, "statements", "kind")? {
+            self.conn.execute("ALTER TABLE statements ADD COLUMN kind TEXT", params![])?;
+        }
+        if options.preserve_order {
+            if !column_exists(&self.conn, "statements", "seq")? {
+                self.conn.execute("ALTER TABLE statements ADD COLUMN seq INTEGER", params![])?;
+            }
+            // Carry on numbering from earlier runs.
+            self.seq_offset = self.conn.query_row(
+                "SELECT coalesce(max(seq), 0) FROM statements",
+                params![],
+                |row| row.get(0),
+            )?;
+        }
+        Ok(())
+    }
+
+    fn insert_rows(&mut self, rows: &[Vec<Option<String>>]) -> Result<(), Box<dyn Error>> {
+        insert_rows(&self.conn, rows, &self.columns, self.seq_offset, self.busy_timeout)?;
+        self.inserted += rows.len();
+        self.uncommitted += rows.len();
+        if self.commit_every.is_some_and(|every| self.uncommitted >= every) {
+            debug!("Committing after {} statements", self.inserted);
+            retry_busy(self.busy_timeout, || self.conn.execute_batch("COMMIT"))?;
+            retry_busy(self.busy_timeout, || self.conn.execute_batch("BEGIN IMMEDIATE"))?;
+            self.uncommitted = 0;
+        }
+        Ok(())
+    }
+
+    fn commit(&mut self) -> Result<(), Box<dyn Error>> {
+        if let Some(ref source) = self.source {
+            self.conn.execute("CREATE TABLE IF NOT EXISTS import_metadata (
+      source TEXT,
+      imported TEXT,
+      version TEXT,
+      statements INTEGER
+    )", params![])?;
+            self.conn.execute(
+                "INSERT INTO import_metadata VALUES (?, datetime('now'), ?, ?)",
+                params![source, env!("CARGO_PKG_VERSION"), self.inserted as i64],
+            )?;
+        }
+        retry_busy(self.busy_timeout, || self.conn.execute_batch("COMMIT"))
+    }
+}
+
+/// Discard every statement, for dry runs.
+pub struct NullSink;
+
+impl StatementSink for NullSink {
+    fn create_table(&mut self, _options: &Options) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    fn insert_rows(&mut self, _rows: &[Vec<Option<String>>]) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    fn commit(&mut self) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+}
+
+const XSD: &str = "http://www.w3.org/2001/XMLSchema#";
+const XSD_STRING: &str = "http://www.w3.org/2001/XMLSchema#string";
+const RDF_XML_LITERAL: &str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#XMLLiteral";
+
+/// Split an optional sign from a number, dropping a redundant `+`.
+fn split_sign(value: &str) -> (&str, &str) {
+    if let Some(rest) = value.strip_prefix('-') {
+        ("-", rest)
+    } else {
+        ("", value.strip_prefix('+').unwrap_or(value))
+    }
+}
+
+fn canonical_integer(value: &str) -> Option<String> {
+    let (sign, digits) = split_sign(value);
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let digits = digits.trim_start_matches('0');
+    if digits.is_empty() {
+        return Some("0".to_string());
+    }
+    Some(format!("{}{}", sign, digits))
+}
+
+/// Is a canonical integer within the range of a bounded integer type, e.g. -128 to 127 for `xsd:byte`?
+/// Unbounded types are always in range.
+fn integer_in_range(value: &str, local: &str) -> bool {
+    let (negative, zero) = (value.starts_with('-'), value == "0");
+    let (min, max): (i128, i128) = match local {
+        "nonNegativeInteger" => return !negative,
+        "positiveInteger" => return !negative && !zero,
+        "nonPositiveInteger" => return negative || zero,
+        "negativeInteger" => return negative,
+        "long" => (i64::MIN.into(), i64::MAX.into()),
+        "int" => (i32::MIN.into(), i32::MAX.into()),
+        "short" => (i16::MIN.into(), i16::MAX.into()),
+        "byte" => (i8::MIN.into(), i8::MAX.into()),
+        "unsignedLong" => (0, u64::MAX.into()),
+        "unsignedInt" => (0, u32::MAX.into()),
+        "unsignedShort" => (0, u16::MAX.into()),
+        "unsignedByte" => (0, u8::MAX.into()),
+        _ => return true,
+    };
+    matches!(value.parse::<i128>(), Ok(n) if min <= n && n <= max)
+}
+
+fn canonical_decimal(value: &str) -> Option<String> {
+    let (sign, number) = split_sign(value);
+    let (whole, fraction) = match number.find('.') {
+        Some(dot) => (&number[..dot], &number[dot + 1..]),
+        None => (number, ""),
+    };
+    if whole.is_empty() && fraction.is_empty()
+        || !whole.bytes().chain(fraction.bytes()).all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let whole = match whole.trim_start_matches('0') {
+        "" => "0",
+        w => w,
+    };
+    let fraction = match fraction.trim_end_matches('0') {
+        "" => "0",
+        f => f,
+    };
+    let sign = if whole == "0" && fraction == "0" { "" } else { sign };
+    Some(format!("{}{}.{}", sign, whole, fraction))
+}
+
+fn canonical_boolean(value: &str) -> Option<String> {
+    match value {
+        "true" | "1" => Some("true".to_string()),
+        "false" | "0" => Some("false".to_string()),
+        _ => None,
+    }
+}
+
+/// Split a trailing `Z` or `+hh:mm`/`-hh:mm` timezone off an XSD date or time value,
+/// returning the rest and the offset in seconds east of UTC, if there is one.
+fn split_timezone(value: &str) -> Option<(&str, Option<i32>)> {
+    if let Some(rest) = value.strip_suffix('Z') {
+        return Some((rest, Some(0)));
+    }
+    let start = value.len().saturating_sub(6);
+    let zone = &value.as_bytes()[start..];
+    if zone.len() < 6 || !(zone[0] == b'+' || zone[0] == b'-') || zone[3] != b':' {
+        return Some((value, None));
+    }
+    let digits = [zone[1], zone[2], zone[4], zone[5]];
+    if !digits.iter().all(u8::is_ascii_digit) {
+        return None;
+    }
+    let number = |tens: u8, ones: u8| i32::from(tens - b'0') * 10 + i32::from(ones - b'0');
+    let (hours, minutes) = (number(zone[1], zone[2]), number(zone[4], zone[5]));
+    if hours > 14 || minutes > 59 {
+        return None;
+    }
+    let offset = (hours * 60 + minutes) * 60;
+    Some((&value[..start], Some(if zone[0] == b'-' { -offset } else { offset })))
+}
+
+/// Fractional seconds without trailing zeros, e.g. `.5`, or nothing for whole seconds.
+fn canonical_fraction(nanoseconds: u32) -> String {
+    if nanoseconds == 0 {
+        return String::new();
+    }
+    format!(".{:09}", nanoseconds).trim_end_matches('0').to_string()
+}
+
+/// Normalize an `xsd:dateTime` to UTC with zero-padded fields and no redundant fractional zeros,
+/// e.g. `2020-1-1T07:00:00.500-05:00` becomes `2020-01-01T12:00:00.5Z`.
+/// A value without a timezone stays without one.
+fn canonical_date_time(value: &str) -> Option<String> {
+    let (local, offset) = split_timezone(value)?;
+    let time = NaiveDateTime::parse_from_str(local, "%Y-%m-%dT%H:%M:%S%.f").ok()?;
+    let (time, zone) = match offset {
+        Some(offset) => (time.checked_sub_signed(chrono::Duration::seconds(offset.into()))?, "Z"),
+        None => (time, ""),
+    };
+    Some(format!("{}{}{}", time.format("%Y-%m-%dT%H:%M:%S"), canonical_fraction(time.nanosecond()), zone))
+}
+
+/// Zero-pad an `xsd:date`, e.g. `2020-1-1+00:00` becomes `2020-01-01Z`.
+/// A date isn't moved to UTC, since that would change the day, so other timezones are kept.
+fn canonical_date(value: &str) -> Option<String> {
+    let (local, offset) = split_timezone(value)?;
+    let date = NaiveDate::parse_from_str(local, "%Y-%m-%d").ok()?;
+    let zone = match offset {
+        None => String::new(),
+        Some(0) => String::from("Z"),
+        Some(offset) => {
+            let sign = if offset < 0 { '-' } else { '+' };
+            format!("{}{:02}:{:02}", sign, offset.abs() / 3600, offset.abs() % 3600 / 60)
+        },
+    };
+    Some(format!("{}{}", date.format("%Y-%m-%d"), zone))
+}
+
+/// Normalize an `xsd:time` like the time of an `xsd:dateTime`, wrapping around midnight,
+/// e.g. `13:00:00.50+01:00` becomes `12:00:00.5Z`.
+fn canonical_time(value: &str) -> Option<String> {
+    let (local, offset) = split_timezone(value)?;
+    let time = NaiveTime::parse_from_str(local, "%H:%M:%S%.f").ok()?;
+    let (time, zone) = match offset {
+        Some(offset) => (time.overflowing_sub_signed(chrono::Duration::seconds(offset.into())).0, "Z"),
+        None => (time, ""),
+    };
+    Some(format!("{}{}{}", time.format("%H:%M:%S"), canonical_fraction(time.nanosecond()), zone))
+}
+
+/// Return the canonical lexical form of a literal with one of the common XSD datatypes,
+/// so that equal values are stored as equal strings.
+/// Other datatypes are returned unchanged; an invalid value,
+/// or an integer out of the range of its type, is an error.
+pub fn canonicalize_literal(value: &str, datatype: &str) -> Result<String, String> {
+    let local = match datatype.strip_prefix(XSD) {
+        Some(local) => local,
+        None => return Ok(value.to_string()),
+    };
+    // These types all collapse surrounding whitespace.
+    let trimmed = value.trim();
+    let canonical = match local {
+        "integer" | "int" | "long" | "short" | "byte"
+        | "nonNegativeInteger" | "positiveInteger" | "nonPositiveInteger" | "negativeInteger"
+        | "unsignedLong" | "unsignedInt" | "unsignedShort" | "unsignedByte" => {
+            let canonical = canonical_integer(trimmed);
+            match canonical {
+                Some(ref n) if !integer_in_range(n, local) => {
+                    return Err(format!("xsd:{} value '{}' is out of range", local, value));
+                },
+                _ => canonical,
+            }
+        },
+        "decimal" => canonical_decimal(trimmed),
+        "boolean" => canonical_boolean(trimmed),
+        "dateTime" => canonical_date_time(trimmed),
+        "date" => canonical_date(trimmed),
+        "time" => canonical_time(trimmed),
+        _ => return Ok(value.to_string()),
+    };
+    canonical.ok_or_else(|| format!("invalid xsd:{} value '{}'", local, value))
+}
+
+fn trim_literal<'a>(options: &Options, value: &'a str) -> &'a str {
+    if options.trim_literals { value.trim() } else { value }
+}
+
+/// The IRI that our fork of rio_xml uses as the subject, predicate, and object of the triple
+/// it emits at the end of each child element of the root element.
+pub const STANZA_END: &str = "http://example.com/stanza-end";
+
+/// Is this the fork's stanza-end triple? Only the whole triple counts,
+/// so a real statement about `STANZA_END` is kept as data.
+fn is_stanza_end(t: &Triple) -> bool {
+    let end = NamedNode { iri: STANZA_END };
+    t.subject == NamedOrBlankNode::from(end) && t.predicate == end && t.object == Term::from(end)
+}
+
+/// Turn the statements of a finished stanza, in the order they were parsed,
+/// into rows with the stanza as their first cell, last statement first.
+/// If no named subject has set the `stanza`, it's the subject of the last statement.
+/// Return the rows and the stanza.
+fn stanza_rows(statements: Vec<Vec<Option<String>>>, stanza: &str) -> (Vec<Vec<Option<String>>>, String) {
+    let stanza = match statements.last() {
+        Some(last) if stanza.is_empty() => last[0].clone().unwrap_or_default(),
+        _ => stanza.to_string(),
+    };
+    let rows = statements.into_iter().rev()
+        .map(|statement| {
+            let mut row = vec![Some(stanza.clone())];
+            row.extend(statement);
+            row
+        })
+        .collect();
+    (rows, stanza)
+}
+
+/// How many examples to give in a warning about a problem that may repeat many times.
+const WARNING_EXAMPLES: usize = 10;
+
+/// The malformed IRIs found by `--validate-iris`: how many, and the first few.
+#[derive(Default)]
+struct IriReport {
+    strict: bool,
+    count: usize,
+    examples: Vec<String>,
+}
+
+impl IriReport {
+    /// Note `iri` if it is malformed, or stop with an error in strict mode.
+    fn check(&mut self, iri: &str, line: usize) -> Result<(), Box<dyn Error>> {
+        if let Err(err) = Iri::parse(iri) {
+            let message = format!("line {}: malformed IRI <{}>: {}", line, iri, err);
+            if self.strict {
+                return Err(RdftabError::Parse(message).into());
+            }
+            self.count += 1;
+            if self.examples.len() < WARNING_EXAMPLES {
+                self.examples.push(message);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parse RDFXML from `input`, shortening IRIs with the given prefixes
+/// (ordered longest base first, as returned by `get_prefixes`),
+/// and call `on_stanza` with the statement rows of each stanza as it is completed.
+/// Each row has seven cells: stanza, subject, predicate, object, value, datatype, language,
+/// then the kind of object if `options.emit_kind` is true,
+/// and the position of the triple in the document if `options.preserve_order` is true
+/// (see `row_columns`).
+pub fn parse_stanzas<R: BufRead>(
+    input: R,
+    base: &str,
+    prefixes: &[Prefix],
+    options: &Options,
+    on_stanza: &mut impl FnMut(Vec<Vec<Option<String>>>) -> Result<(), Box<dyn Error>>,
+) -> Result<(), Box<dyn Error>> {
+    let exclude = options.excluded_predicates(prefixes);
+    // Without prefixes, `shorten` keeps every IRI whole.
+    let prefixes = if options.no_shorten { &[] } else { prefixes };
+    let annotated_source = NamedNode { iri: "http://www.w3.org/2002/07/owl#annotatedSource" };
+    let reified_source = NamedNode { iri: "http://www.w3.org/1999/02/22-rdf-syntax-ns#subject" };
+    let mut stack: Vec<Vec<Option<String>>> = Vec::new();
+    let mut stanza = String::from("");
+    // The stanzas of earlier annotation axioms and reified statements, by blank node,
+    // for annotations on those annotations.
+    let mut axiom_stanzas: BTreeMap<String, String> = BTreeMap::new();
+    let mut axioms: Vec<String> = Vec::new();
+    let mut seq = 0;
+    let line = Rc::new(Cell::new(1));
+    let input = LineCounter { inner: input, line: line.clone() };
+    let limit = options.limit.unwrap_or(usize::MAX);
+    let completed = Cell::new(0);
+    let mut iris = IriReport { strict: options.strict, ..IriReport::default() };
+    let mut parser = RdfXmlParser::new(input, base)?;
+    let mut on_triple = |t: Triple| -> Result<(), Box<dyn Error>> {
+        if completed.get() >= limit {
+            // Skip what's left of the parser's current step once we have enough stanzas.
+            Ok(())
+        } else if is_stanza_end(&t) {
+            completed.set(completed.get() + 1);
+            let (rows, name) = stanza_rows(std::mem::take(&mut stack), &std::mem::take(&mut stanza));
+            for axiom in axioms.drain(..) {
+                axiom_stanzas.insert(axiom, name.clone());
+            }
+            on_stanza(rows)
+        } else if exclude.contains(t.predicate.iri) {
+            // Before anything else, so that an excluded predicate can't place an annotation in a stanza.
+            options.counters.excluded.fetch_add(1, Ordering::Relaxed);
+            Ok(())
+        } else {
+            if options.validate_iris {
+                if let NamedOrBlankNode::NamedNode(node) = t.subject {
+                    iris.check(node.iri, line.get())?;
+                }
+                iris.check(t.predicate.iri, line.get())?;
+                if let Term::NamedNode(node) = t.object {
+                    iris.check(node.iri, line.get())?;
+                }
+            }
+            let subject = match t.subject {
+                NamedOrBlankNode::NamedNode(node) => Some(shorten(prefixes, node.iri)),
+                NamedOrBlankNode::BlankNode(node) => Some(format!("_:{}{}", options.bnode_prefix, node.id)),
+            };
+            let predicate = Some(shorten(prefixes, t.predicate.iri));
+            let (object, value, datatype, language) = match t.object {
+                Term::NamedNode(node) => (Some(shorten(prefixes, node.iri)), None, None, None),
+                Term::BlankNode(node) => (Some(format!("_:{}{}", options.bnode_prefix, node.id)), None, None, None),
+                Term::Literal(node) => match node {
+                    Literal::Simple { value } => (None, Some(trim_literal(options, value).to_string()), None, None),
+                    // RDF 1.1 makes xsd:string the datatype of every simple literal.
+                    Literal::Typed { value, datatype } if datatype.iri == XSD_STRING => {
+                        (None, Some(trim_literal(options, value).to_string()), None, None)
+                    },
+                    // Whitespace in XML markup is part of the value.
+                    Literal::Typed { value, datatype } if datatype.iri == RDF_XML_LITERAL => {
+                        (None, Some(value.to_string()), Some(shorten(prefixes, datatype.iri)), None)
+                    },
+                    Literal::Typed { value, datatype } => {
+                        let value = trim_literal(options, value);
+                        let value = if options.canonicalize_literals {
+                            match canonicalize_literal(value, datatype.iri) {
+                                Ok(canonical) => canonical,
+                                Err(message) => {
+                                    options.warn(&message)?;
+                                    value.to_string()
+                                },
+                            }
+                        } else {
+                            value.to_string()
+                        };
+                        (None, Some(value), Some(shorten(prefixes, datatype.iri)), None)
+                    },
+                    Literal::LanguageTaggedString { value, language } => {
+                        // Language tags are case-insensitive, so pick one case to compare them.
+                        let language = if options.normalize_lang { language.to_lowercase() } else { language.to_string() };
+                        (None, Some(trim_literal(options, value).to_string()), None, Some(language))
+                    },
+                },
+            };
+            let mut row = vec![subject, predicate, object, value, datatype, language];
+            if options.emit_kind {
+                let kind = match t.object {
+                    Term::NamedNode(_) => "iri",
+                    Term::BlankNode(_) => "bnode",
+                    Term::Literal(_) => "literal",
+                };
+                row.push(Some(kind.to_string()));
+            }
+            if options.preserve_order {
+                seq += 1;
+                row.push(Some(seq.to_string()));
+            }
+            stack.push(row);
+
+            if let NamedOrBlankNode::NamedNode(node) = t.subject {
+                stanza = shorten(prefixes, node.iri);
+            }
+            if t.predicate == annotated_source || t.predicate == reified_source {
+                if let NamedOrBlankNode::BlankNode(node) = t.subject {
+                    axioms.push(format!("_:{}{}", options.bnode_prefix, node.id));
+                }
+                if stanza.is_empty() {
+                    match t.object {
+                        Term::NamedNode(node) => stanza = shorten(prefixes, node.iri),
+                        // An annotation on an earlier annotation axiom goes with that axiom.
+                        Term::BlankNode(node) => {
+                            if let Some(source) = axiom_stanzas.get(&format!("_:{}{}", options.bnode_prefix, node.id)) {
+                                stanza = source.clone();
+                            }
+                        },
+                        _ => (),
+                    }
+                }
+            }
+            Ok(())
+        }
+    };
+    // Parse a step at a time, so that we can stop reading once we have enough stanzas.
+    let mut result = Ok(());
+    while result.is_ok() && !parser.is_end() && completed.get() < limit {
+        result = parser.parse_step(&mut on_triple);
+    }
+    result.map_err(|err| {
+        if err.is::<RdfXmlError>() {
+            RdftabError::Parse(format!("error at line {}: {}", line.get(), err)).into()
+        } else {
+            err
+        }
+    })?;
+    if iris.count > 0 {
+        options.warn(&format!("{}: found {} malformed IRIs, starting with:\n  {}", base, iris.count, iris.examples.join("\n  ")))?;
+    }
+    Ok(())
+}
+
+/// Convert an RDFXML document to statement rows without touching a database.
+pub fn rdf_to_rows(input: &str, prefixes: &[Prefix]) -> Result<Vec<Vec<Option<String>>>, Box<dyn Error>> {
+    let mut rows = Vec::new();
+    let input = input.strip_prefix('\u{feff}').unwrap_or(input);
+    parse_stanzas(input.as_bytes(), "", prefixes, &Options::default(), &mut |stanza| {
+        rows.extend(stanza);
+        Ok(())
+    })?;
+    Ok(rows)
+}
+
+const COLUMNS: [&str; 7] = ["stanza", "subject", "predicate", "object", "value", "datatype", "language"];
+
+/// The columns of the rows that `store` inserts with these options:
+/// the seven statement columns, then `kind` and `seq` if they were asked for
+/// (as from `parse_stanzas`), then `graph` if there is one.
+pub fn row_columns(options: &Options) -> Vec<&'static str> {
+    let mut columns = COLUMNS.to_vec();
+    if options.emit_kind {
+        columns.push("kind");
+    }
+    if options.preserve_order {
+        columns.push("seq");
+    }
+    if options.graph.is_some() {
+        columns.push("graph");
+    }
+    columns
+}
+
+/// Convert a statement row to a JSON object with a key for each of its `columns`.
+pub fn row_to_json(columns: &[&str], row: &[Option<String>]) -> SerdeValue {
+    let mut object = SerdeMap::new();
+    for (column, cell) in columns.iter().zip(row) {
+        object.insert(column.to_string(), cell.clone().into());
+    }
+    SerdeValue::Object(object)
+}
+
+/// Store the stanzas that `produce` passes to its callback in `sink`:
+/// create the table, skip duplicates, insert in batches, commit once at the end,
+/// and print a summary.
+fn store(
+    options: &Options,
+    prefixes: &[Prefix],
+    sink: &mut dyn StatementSink,
+    produce: impl FnOnce(&mut dyn FnMut(Vec<Vec<Option<String>>>) -> Result<(), Box<dyn Error>>) -> Result<(), Box<dyn Error>>,
+) -> Result<(), Box<dyn Error>> {
+    let start = Instant::now();
+    let warnings = options.counters.warnings.load(Ordering::Relaxed);
+    let excluded = options.counters.excluded.load(Ordering::Relaxed);
+    let mut batch: Vec<Vec<Option<String>>> = Vec::new();
+    let mut seen: BTreeSet<Vec<Option<String>>> = BTreeSet::new();
+    let mut duplicates: usize = 0;
+    let mut outside = 0;
+    let mut parsed = 0;
+    let mut stanzas: usize = 0;
+    let mut inserted: usize = 0;
+    // Match subjects whether or not they were shortened.
+    let subject_prefixes = match options.subject_prefix {
+        Some(ref prefix) => {
+            let iri = expand(prefixes, prefix);
+            vec![shorten(prefixes, &iri), iri]
+        },
+        None => Vec::new(),
+    };
+    let wanted = |term: &Option<String>| {
+        term.as_ref().is_some_and(|term| subject_prefixes.iter().any(|prefix| term.starts_with(prefix.as_str())))
+    };
+    let columns = row_columns(options);
+    sink.create_table(options)?;
+    let mut progress = Progress::new();
+    let stdout = io::stdout();
+    let mut json = io::BufWriter::new(stdout.lock());
+    produce(&mut |mut rows| {
+        parsed += rows.len();
+        progress.update(parsed);
+        if !subject_prefixes.is_empty() {
+            // Keep blank nodes along with the stanza they belong to.
+            let before = rows.len();
+            rows.retain(|row| match row[1] {
+                Some(ref subject) if subject.starts_with("_:") => wanted(&row[0]),
+                _ => wanted(&row[1]),
+            });
+            outside += before - rows.len();
+        }
+        if rows.is_empty() {
+            return Ok(());
+        }
+        if options.thick {
+            rows = thicken(rows);
+        }
+        stanzas += 1;
+        for mut row in rows {
+            if options.dedup && !seen.insert(row[1..7].to_vec()) {
+                duplicates += 1;
+                continue;
+            }
+            if options.graph.is_some() {
+                row.push(options.graph.clone());
+            }
+            if options.json {
+                writeln!(json, "{}", row_to_json(&columns, &row))?;
+            }
+            batch.push(row);
+            inserted += 1;
+        }
+        while batch.len() >= options.batch_size {
+            let rest = batch.split_off(options.batch_size);
+            sink.insert_rows(&batch)?;
+            batch = rest;
+        }
+        Ok(())
+    })?;
+    if !batch.is_empty() {
+        sink.insert_rows(&batch)?;
+    }
+    sink.commit()?;
+    json.flush()?;
+    drop(progress);
+    let excluded = options.counters.excluded.load(Ordering::Relaxed) - excluded;
+    // Excluded statements never reach `produce`'s callback, but they were parsed.
+    let parsed = parsed + excluded;
+    if !options.exclude_predicates.is_empty() {
+        info!("Excluded {} statements by predicate", excluded);
+    }
+    if options.subject_prefix.is_some() {
+        info!("Skipped {} statements about other subjects", outside);
+    }
+    if options.dedup {
+        info!("Removed {} duplicate statements", duplicates);
+    }
+    if options.dry_run {
+        info!("Dry run: parsed {} triples, would insert {} statements across {} stanzas", parsed, inserted, stanzas);
+    } else {
+        info!("Parsed {} triples, inserted {} statements across {} stanzas", parsed, inserted, stanzas);
+    }
+    if let Some(ref path) = options.stats_json {
+        let mut stats = SerdeMap::new();
+        stats.insert("triples".to_string(), parsed.into());
+        stats.insert("stanzas".to_string(), stanzas.into());
+        stats.insert("inserted".to_string(), inserted.into());
+        stats.insert("duplicates".to_string(), duplicates.into());
+        stats.insert("excluded".to_string(), excluded.into());
+        stats.insert("outside_subject_prefix".to_string(), outside.into());
+        stats.insert("warnings".to_string(), (options.counters.warnings.load(Ordering::Relaxed) - warnings).into());
+        stats.insert("elapsed_seconds".to_string(), start.elapsed().as_secs_f64().into());
+        stats.insert("dry_run".to_string(), options.dry_run.into());
+        fs::write(path, format!("{}\n", serde_json::to_string_pretty(&SerdeValue::Object(stats))?))?;
+    }
+    Ok(())
+}
+
+/// The default blank node label prefix for a named graph:
+/// its ASCII letters and digits, with `_` for anything else, e.g. `ex_a-` for `ex:a`.
+fn graph_bnode_prefix(graph: &str) -> String {
+    let name: String = graph.chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '_' }).collect();
+    format!("{}-", name)
+}
+
+/// Parse each RDFXML input in turn, resolving relative IRIs against its base,
+/// and store the statements in `sink`, committing once at the end.
+/// With more than one input, blank node labels get a per-input prefix so they can't collide.
+pub fn load<R: BufRead>(
+    inputs: Vec<(R, String)>,
+    prefixes: &[Prefix],
+    options: &Options,
+    sink: &mut dyn StatementSink,
+) -> Result<(), Box<dyn Error>> {
+    let count = inputs.len();
+    // Loading the same file into another graph shouldn't reuse its blank node labels.
+    let bnode_prefix = match options.graph {
+        Some(ref graph) if options.bnode_prefix.is_empty() => graph_bnode_prefix(graph),
+        _ => options.bnode_prefix.clone(),
+    };
+    store(options, prefixes, sink, |on_stanza| {
+        let mut parsed = 0;
+        let mut stanzas = 0;
+        for (i, (input, base)) in inputs.into_iter().enumerate() {
+            let mut input_options = options.clone();
+            input_options.bnode_prefix = if count > 1 { format!("{}f{}-", bnode_prefix, i + 1) } else { bnode_prefix.clone() };
+            // The limit is for all the inputs together.
+            input_options.limit = options.limit.map(|limit| limit - stanzas);
+            if input_options.limit == Some(0) {
+                break;
+            }
+            // Number the triples of later inputs after those of earlier ones.
+            let offset = parsed;
+            debug!("Parsing {}", base);
+            parse_stanzas(input, &base, prefixes, &input_options, &mut |mut rows| {
+                parsed += rows.len();
+                stanzas += 1;
+                if offset > 0 && options.preserve_order {
+                    // The seq cell is always last.
+                    for seq in rows.iter_mut().filter_map(|row| row.last_mut()) {
+                        if let Some(n) = seq.take() {
+                            *seq = Some((n.parse::<usize>()? + offset).to_string());
+                        }
+                    }
+                }
+                on_stanza(rows)
+            })?;
+        }
+        Ok(())
+    })
+}
+
+/// Read statements from CSV inputs (or TSV, with a tab `delimiter`) and store them in `sink`.
+/// The header row must name `subject` and `predicate` columns, and may name
+/// `object`, `value`, `datatype`, `language`, and `stanza` columns, in any order.
+/// Empty cells are NULL, except that a statement with no object has an empty value.
+/// Without a `stanza` column, the stanza of a named subject is the subject itself.
+/// Consecutive rows with the same stanza are stored as one stanza.
+pub fn load_csv<R: Read>(
+    inputs: Vec<(R, String)>,
+    delimiter: u8,
+    prefixes: &[Prefix],
+    options: &Options,
+    sink: &mut dyn StatementSink,
+) -> Result<(), Box<dyn Error>> {
+    let exclude = options.excluded_predicates(prefixes);
+    store(options, prefixes, sink, |on_stanza| {
+        let limit = options.limit.unwrap_or(usize::MAX);
+        let mut stanzas = 0;
+        // Number the rows across all the inputs, like `load` numbers triples.
+        let mut seq = 0;
+        for (input, name) in inputs {
+            if stanzas >= limit {
+                break;
+            }
+            debug!("Reading {}", name);
+            let mut reader = csv::ReaderBuilder::new().delimiter(delimiter).from_reader(input);
+            let headers = reader.headers()?.clone();
+            let columns: Vec<Option<usize>> = COLUMNS.iter()
+                .map(|column| headers.iter().position(|header| header.trim() == *column))
+                .collect();
+            if columns[1].is_none() || columns[2].is_none() {
+                return Err(RdftabError::Parse(format!("{}: the header must have subject and predicate columns", name)).into());
+            }
+            let mut rows: Vec<Vec<Option<String>>> = Vec::new();
+            let mut incomplete: Vec<u64> = Vec::new();
+            for record in reader.records() {
+                let record = record?;
+                let line = record.position().map(|p| p.line()).unwrap_or(0);
+                let mut row: Vec<Option<String>> = columns.iter()
+                    .map(|column| column.and_then(|i| record.get(i)).filter(|cell| !cell.is_empty()).map(String::from))
+                    .collect();
+                if row[1].is_none() || row[2].is_none() {
+                    if options.strict {
+                        return Err(RdftabError::Parse(format!("{}:{}: missing subject or predicate", name, line)).into());
+                    }
+                    incomplete.push(line);
+                    continue;
+                }
+                if row[2].as_ref().is_some_and(|predicate| exclude.contains(&expand(prefixes, predicate))) {
+                    options.counters.excluded.fetch_add(1, Ordering::Relaxed);
+                    continue;
+                }
+                if row[3].is_none() && row[4].is_none() {
+                    row[4] = Some(String::new());
+                }
+                if row[5].is_some() && row[6].is_some() {
+                    options.warn(&format!("{}:{}: a literal can't have both a datatype and a language; ignoring the datatype", name, line))?;
+                    row[5] = None;
+                }
+                if row[0].is_none() && !row[1].as_ref().is_some_and(|s| s.starts_with("_:")) {
+                    row[0] = row[1].clone();
+                }
+                if options.emit_kind {
+                    let kind = match row[3] {
+                        Some(ref object) if object.starts_with("_:") => "bnode",
+                        Some(_) => "iri",
+                        None => "literal",
+                    };
+                    row.push(Some(kind.to_string()));
+                }
+                if options.preserve_order {
+                    seq += 1;
+                    row.push(Some(seq.to_string()));
+                }
+                if rows.last().is_some_and(|last| last[0] != row[0]) {
+                    on_stanza(std::mem::take(&mut rows))?;
+                    stanzas += 1;
+                    if stanzas >= limit {
+                        break;
+                    }
+                }
+                rows.push(row);
+            }
+            if !rows.is_empty() && stanzas < limit {
+                on_stanza(rows)?;
+                stanzas += 1;
+            }
+            if !incomplete.is_empty() {
+                let lines: Vec<String> = incomplete.iter().take(WARNING_EXAMPLES).map(|line| line.to_string()).collect();
+                options.warn(&format!(
+                    "{}: skipped {} rows missing a subject or predicate, on lines {}{}",
+                    name, incomplete.len(), lines.join(", "), if incomplete.len() > lines.len() { ", ..." } else { "" }
+                ))?;
+            }
+        }
+        Ok(())
+    })
+}
+
+/// Is this input an HTTP(S) URL rather than a file path?
+fn is_url(path: &str) -> bool {
+    path.starts_with("http://") || path.starts_with("https://")
+}
+
+/// Fetch an input URL, following redirects, and stream the response body.
+#[cfg(feature = "url")]
+fn open_url(url: &str) -> Result<Box<dyn Read>, Box<dyn Error>> {
+    match ureq::get(url).call() {
+        Ok(response) => Ok(response.into_reader()),
+        Err(ureq::Error::Status(code, response)) => Err(RdftabError::NotFound(format!(
+            "Cannot open {}: HTTP {} {}", url, code, response.status_text())).into()),
+        Err(ureq::Error::Transport(err)) => Err(RdftabError::NotFound(format!("Cannot open {}: {}", url, err)).into()),
+    }
+}
+
+#[cfg(not(feature = "url"))]
+fn open_url(url: &str) -> Result<Box<dyn Read>, Box<dyn Error>> {
+    Err(RdftabError::Usage(format!("Cannot open {}: rdftab was built without the `url` feature", url)).into())
+}
+
+/// Open an input file or URL with `open_reader`.
+fn open_input(path: &str) -> Result<Box<dyn BufRead>, Box<dyn Error>> {
+    if is_url(path) {
+        return Ok(open_reader(io::BufReader::new(open_url(path)?))?);
+    }
+    let file = fs::File::open(path).map_err(|err| open_error(path, err))?;
+    Ok(open_reader(io::BufReader::new(file))?)
+}
+
+/// Print the number of triples in the RDFXML input files or STDIN
+/// (or the number of rows, for CSV and TSV), without building stanzas or using a database.
+pub fn count(inputs: &[String], format: &str) -> Result<(), Box<dyn Error>> {
+    if !["rdfxml", "csv", "tsv"].contains(&format) {
+        return Err(RdftabError::Usage(format!("unknown input format '{}'", format)).into());
+    }
+    let stdin = io::stdin();
+    let mut readers: Vec<(Box<dyn BufRead>, String)> = Vec::new();
+    if inputs.is_empty() {
+        readers.push((open_reader(stdin.lock())?, "stdin".to_string()));
+    } else {
+        for path in inputs {
+            readers.push((open_input(path)?, path.to_string()));
+        }
+    }
+    let mut total = 0;
+    for (input, name) in readers {
+        debug!("Counting {}", name);
+        match format {
+            "rdfxml" => {
+                let line = Rc::new(Cell::new(1));
+                let input = LineCounter { inner: input, line: line.clone() };
+                let mut parser = RdfXmlParser::new(input, &format!("file:{}", name))?;
+                parser.parse_all(&mut |t: Triple| -> Result<(), RdfXmlError> {
+                    if !is_stanza_end(&t) {
+                        total += 1;
+                    }
+                    Ok(())
+                }).map_err(|err| RdftabError::Parse(format!("{}: error at line {}: {}", name, line.get(), err)))?;
+            },
+            _ => {
+                let delimiter = if format == "tsv" { b'\t' } else { b',' };
+                let mut reader = csv::ReaderBuilder::new().delimiter(delimiter).from_reader(input);
+                for record in reader.records() {
+                    record?;
+                    total += 1;
+                }
+            },
+        }
+    }
+    println!("{}", total);
+    Ok(())
+}
+
+/// Read RDFXML (or CSV or TSV statements, depending on `options.format`)
+/// from the input files or STDIN into the statements table of the SQLite database `db`.
+pub fn insert(db: &str, options: &Options) -> Result<(), Box<dyn Error>> {
+    if !["rdfxml", "csv", "tsv"].contains(&options.format.as_str()) {
+        return Err(RdftabError::Usage(format!("unknown input format '{}'", options.format)).into());
+    }
+    if let Some(ref base) = options.base {
+        if let Err(err) = Iri::parse(base.as_str()) {
+            return Err(RdftabError::Usage(format!("invalid base IRI <{}>: {}", base, err)).into());
+        }
+    }
+    // An xml:base in the document still takes precedence over these.
+    let base_for = |name: &str| options.base.clone().unwrap_or_else(|| {
+        if is_url(name) { name.to_string() } else { format!("file:{}", name) }
+    });
+    let stdin = io::stdin();
+    let mut inputs: Vec<(Box<dyn BufRead>, String)> = Vec::new();
+    if options.inputs.is_empty() {
+        inputs.push((open_reader(stdin.lock())?, base_for(db)));
+    } else {
+        for path in &options.inputs {
+            inputs.push((open_input(path)?, base_for(path)));
+        }
+    }
+    let mut conn = if options.dry_run {
+        // Work on a scratch copy of the prefixes so that the target is never written.
+        let mut memory = Connection::open_in_memory()?;
+        if Path::new(db).exists() {
+            let mut target = Connection::open_with_flags(db, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+            if table_exists(&target, "prefix")? {
+                store_prefixes(&mut memory, &get_prefixes(&mut target)?)?;
+            }
+        }
+        memory
+    } else {
+        let conn = Connection::open(db)?;
+        conn.busy_timeout(Duration::from_millis(options.busy_timeout_ms))?;
+        conn
+    };
+    create_prefix_table(&conn)?;
+    if let Some(ref path) = options.prefixes {
+        let file_prefixes = read_prefix_file(path)?;
+        debug!("Loading {} prefixes from {}", file_prefixes.len(), path);
+        store_prefixes(&mut conn, &file_prefixes)?;
+    }
+    if let Some(ref path) = options.prefixes_json {
+        let json_prefixes = read_prefix_json(path)?;
+        debug!("Loading {} prefixes from {}", json_prefixes.len(), path);
+        let existing = get_prefixes(&mut conn)?;
+        for prefix in &json_prefixes {
+            if let Some(old) = existing.iter().find(|p| p.prefix == prefix.prefix && p.base != prefix.base) {
+                options.warn(&format!("{}: replacing prefix '{}' <{}> with <{}>", path, prefix.prefix, old.base, prefix.base))?;
+            }
+        }
+        store_prefixes(&mut conn, &json_prefixes)?;
+    }
+    let mut prefixes = get_prefixes(&mut conn)?;
+    let mut heads = vec![Vec::new(); inputs.len()];
+    if prefixes.is_empty() && !options.no_auto_prefix && options.format == "rdfxml" {
+        for ((input, base), head) in inputs.iter_mut().zip(heads.iter_mut()) {
+            let (bytes, namespaces) = read_namespaces(input)?;
+            debug!("Found {} xmlns prefixes in {}", namespaces.len(), base);
+            *head = bytes;
+            store_prefixes(&mut conn, &namespaces)?;
+        }
+        prefixes = get_prefixes(&mut conn)?;
+    }
+    if prefixes.is_empty() && !options.no_default_prefixes {
+        debug!("Using default prefixes");
+        store_prefixes(&mut conn, &default_prefixes())?;
+        prefixes = get_prefixes(&mut conn)?;
+    }
+    debug!("Parsing with {} prefixes", prefixes.len());
+    let inputs = inputs.into_iter().zip(heads)
+        .map(|((input, base), head)| (io::Cursor::new(head).chain(input), base))
+        .collect();
+    let load_into = |sink: &mut dyn StatementSink| match options.format.as_str() {
+        "csv" => load_csv(inputs, b',', &prefixes, options, sink),
+        "tsv" => load_csv(inputs, b'\t', &prefixes, options, sink),
+        _ => load(inputs, &prefixes, options, sink),
+    };
+    if options.dry_run {
+        load_into(&mut NullSink)
+    } else {
+        let mut journal_mode: Option<String> = None;
+        if options.fast {
+            journal_mode = Some(conn.query_row("PRAGMA journal_mode", params![], |row| row.get(0))?);
+            // Pragmas can't change the journal mode inside a transaction, so set them first.
+            conn.execute_batch("PRAGMA journal_mode = WAL;
+              PRAGMA synchronous = OFF;
+              PRAGMA cache_size = -100000;")?;
+        }
+        let mut sink = SqliteSink::new(conn, Duration::from_millis(options.busy_timeout_ms))?;
+        if let Some(rows) = options.commit_every {
+            sink.commit_every(rows);
+        }
+        if !options.no_metadata {
+            if options.inputs.is_empty() {
+                sink.record_source("stdin");
+            } else {
+                sink.record_source(&options.inputs.join(" "));
+            }
+        }
+        load_into(&mut sink)?;
+        let conn = sink.into_connection();
+        if let Some(mode) = journal_mode {
+            // Leave the database in the journal mode we found it in, so other tools can open it as before.
+            debug!("Restoring journal_mode {}", mode);
+            conn.execute_batch(&format!("PRAGMA journal_mode = {};", mode))?;
+        }
+        if let Some(ref path) = options.save {
+            debug!("Saving database to {}", path);
+            conn.backup(DatabaseName::Main, path, None)?;
+            info!("Saved database to {}", path);
+        }
+        Ok(())
+    }
+}
+
+fn dot_escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+pub fn graph(db: &str, format: &str) -> Result<(), Box<dyn Error>> {
+    if format != "dot" && format != "mermaid" {
+        return Err(RdftabError::Usage(format!("unknown graph format '{}'", format)).into());
+    }
+    let mut conn = open_existing(db, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let prefixes = get_prefixes(&mut conn)?;
+    // A database loaded with `--no-shorten` has full IRIs, whatever its prefixes.
+    let subclass_of = "http://www.w3.org/2000/01/rdf-schema#subClassOf";
+    let label = "http://www.w3.org/2000/01/rdf-schema#label";
+
+    let mut labels = BTreeMap::new();
+    let mut stmt = conn.prepare("SELECT subject, min(value) FROM statements
+      WHERE predicate IN (?1, ?2) AND value IS NOT NULL
+      GROUP BY subject")?;
+    let mut rows = stmt.query(params![shorten(&prefixes, label), label])?;
+    while let Some(row) = rows.next()? {
+        labels.insert(row.get::<_, String>(0)?, row.get::<_, String>(1)?);
+    }
+
+    // Only edges between named classes: blank node objects are class expressions.
+    let mut edges = Vec::new();
+    let mut nodes = BTreeSet::new();
+    let mut stmt = conn.prepare("SELECT DISTINCT subject, object FROM statements
+      WHERE predicate IN (?1, ?2)
+        AND object IS NOT NULL
+        AND substr(subject, 1, 2) != '_:'
+        AND substr(object, 1, 2) != '_:'
+      ORDER BY subject, object")?;
+    let mut rows = stmt.query(params![shorten(&prefixes, subclass_of), subclass_of])?;
+    while let Some(row) = rows.next()? {
+        let (child, parent): (String, String) = (row.get(0)?, row.get(1)?);
+        nodes.insert(child.clone());
+        nodes.insert(parent.clone());
+        edges.push((child, parent));
+    }
+
+    let label_of = |node: &String| labels.get(node).unwrap_or(node).clone();
+    let stdout = io::stdout();
+    let mut out = io::BufWriter::new(stdout.lock());
+    match format {
+        "dot" => {
+            writeln!(out, "digraph {{")?;
+            writeln!(out, "  rankdir=BT;")?;
+            for node in &nodes {
+                writeln!(out, "  \"{}\" [label=\"{}\"];", dot_escape(node), dot_escape(&label_of(node)))?;
+            }
+            for (child, parent) in &edges {
+                writeln!(out, "  \"{}\" -> \"{}\";", dot_escape(child), dot_escape(parent))?;
+            }
+            writeln!(out, "}}")?;
+        },
+        _ => {
+            // Mermaid node ids cannot contain CURIE punctuation, so number them.
+            let ids: BTreeMap<&String, usize> = nodes.iter().enumerate().map(|(i, n)| (n, i)).collect();
+            writeln!(out, "graph BT")?;
+            for node in &nodes {
+                writeln!(out, "  n{}[\"{}\"]", ids[node], label_of(node).replace('"', "#quot;"))?;
+            }
+            for (child, parent) in &edges {
+                writeln!(out, "  n{} --> n{}", ids[child], ids[parent])?;
+            }
+        },
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// The ORDER BY clause for statements: parse order if there's a `seq` column, otherwise insertion order.
+fn statement_order(conn: &Connection) -> Result<&'static str> {
+    Ok(if column_exists(conn, "statements", "seq")? { "seq, rowid" } else { "rowid" })
+}
+
+/// Read every row of the statements table, in the order they were inserted,
+/// or in the order they were parsed if the table has a `seq` column.
+/// Each row has the same seven cells as the rows from `parse_stanzas`.
+pub fn read_statements(conn: &Connection) -> Result<Vec<Vec<Option<String>>>> {
+    let mut stmt = conn.prepare(&format!("SELECT stanza, subject, predicate, object, value, datatype, language
+      FROM statements ORDER BY {}", statement_order(conn)?))?;
+    let mut rows = stmt.query(params![])?;
+    let mut statements = Vec::new();
+    while let Some(row) = rows.next()? {
+        let mut cells = Vec::new();
+        for i in 0..7 {
+            cells.push(row.get(i)?);
+        }
+        statements.push(cells);
+    }
+    Ok(statements)
+}
+
+/// Collect the statements about `subject` as a JSON object from predicates to arrays of objects,
+/// e.g. `{"rdfs:label": [{"value": "foo", "language": "en"}], "rdf:type": [{"object": "owl:Class"}]}`.
+/// Blank node objects are replaced by the JSON object for that blank node, recursively.
+pub fn subject_to_json(conn: &Connection, subject: &str) -> Result<SerdeValue> {
+    subject_json(conn, subject, &mut Vec::new())
+}
+
+/// How deeply to nest blank nodes in `subject_to_json` and `diff`,
+/// so that a very deep structure can't overflow the stack.
+pub const MAX_NESTING: usize = 256;
+
+/// Build the JSON for `subject`, where `path` holds the blank nodes we're already inside.
+/// A blank node that refers back to one of those is left as its label, to avoid looping forever,
+/// as is one nested more than `MAX_NESTING` deep.
+fn subject_json(conn: &Connection, subject: &str, path: &mut Vec<String>) -> Result<SerdeValue> {
+    let mut rows = Vec::new();
+    {
+        let mut stmt = conn.prepare_cached("SELECT predicate, object, value, datatype, language
+          FROM statements WHERE subject = ?1 ORDER BY rowid")?;
+        let mut results = stmt.query(params![subject])?;
+        while let Some(row) = results.next()? {
+            let cells: (String, Option<String>, Option<String>, Option<String>, Option<String>) =
+                (row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?);
+            rows.push(cells);
+        }
+    }
+    path.push(subject.to_string());
+    let mut predicates = SerdeMap::new();
+    for (predicate, object, value, datatype, language) in rows {
+        let mut cell = SerdeMap::new();
+        match object {
+            Some(ref object) if object.starts_with("_:") && !path.contains(object) && path.len() < MAX_NESTING => {
+                cell.insert("object".to_string(), subject_json(conn, object, path)?);
+            },
+            Some(object) => {
+                cell.insert("object".to_string(), SerdeValue::String(object));
+            },
+            None => cell = literal_cell(value, datatype, language),
+        }
+        push_cell(&mut predicates, predicate, cell);
+    }
+    path.pop();
+    Ok(SerdeValue::Object(predicates))
+}
+
+/// The JSON for a literal object: its value, and its datatype or language if it has one.
+fn literal_cell(value: Option<String>, datatype: Option<String>, language: Option<String>) -> SerdeMap<String, SerdeValue> {
+    let mut cell = SerdeMap::new();
+    cell.insert("value".to_string(), value.into());
+    if let Some(datatype) = datatype {
+        cell.insert("datatype".to_string(), SerdeValue::String(datatype));
+    }
+    if let Some(language) = language {
+        cell.insert("language".to_string(), SerdeValue::String(language));
+    }
+    cell
+}
+
+/// Add an object to the array for `predicate`.
+fn push_cell(predicates: &mut SerdeMap<String, SerdeValue>, predicate: String, cell: SerdeMap<String, SerdeValue>) {
+    if let SerdeValue::Array(objects) = predicates.entry(predicate).or_insert_with(|| SerdeValue::Array(Vec::new())) {
+        objects.push(SerdeValue::Object(cell));
+    }
+}
+
+/// The rows about blank nodes that `thicken` may nest, by subject.
+type Nestable = BTreeMap<String, Vec<Vec<Option<String>>>>;
+
+/// Take the rows about `node` out of `nestable` and build its JSON, as in `subject_to_json`,
+/// or return None if there are none or we're already `MAX_NESTING` deep.
+fn nest(node: &str, nestable: &mut Nestable, depth: usize) -> Option<SerdeValue> {
+    if depth >= MAX_NESTING {
+        return None;
+    }
+    let rows = nestable.remove(node)?;
+    let mut predicates = SerdeMap::new();
+    for mut row in rows {
+        let mut cell = SerdeMap::new();
+        match row[3].take() {
+            Some(object) => {
+                let value = nest(&object, nestable, depth + 1).unwrap_or(SerdeValue::String(object));
+                cell.insert("object".to_string(), value);
+            },
+            None => cell = literal_cell(row[4].take(), row[5].take(), row[6].take()),
+        }
+        push_cell(&mut predicates, row[2].take().unwrap_or_default(), cell);
+    }
+    Some(SerdeValue::Object(predicates))
+}
+
+/// For `--thick`: replace each blank node that is the object of just one statement in the stanza
+/// with JSON for the statements about it, in the `object` column, instead of separate rows.
+pub fn thicken(rows: Vec<Vec<Option<String>>>) -> Vec<Vec<Option<String>>> {
+    let mut uses: BTreeMap<String, usize> = BTreeMap::new();
+    for object in rows.iter().filter_map(|row| row[3].as_ref()).filter(|object| object.starts_with("_:")) {
+        *uses.entry(object.clone()).or_default() += 1;
+    }
+    let mut nestable = Nestable::new();
+    let mut rest = Vec::new();
+    for row in rows {
+        match row[1] {
+            Some(ref subject) if uses.get(subject) == Some(&1) => nestable.entry(subject.clone()).or_default().push(row),
+            _ => rest.push(row),
+        }
+    }
+    let mut thick: Vec<Vec<Option<String>>> = rest.into_iter()
+        .map(|mut row| {
+            if let Some(json) = row[3].clone().and_then(|object| nest(&object, &mut nestable, 1)) {
+                row[3] = Some(json.to_string());
+            }
+            row
+        })
+        .collect();
+    // Keep anything we couldn't nest, such as cycles of blank nodes.
+    thick.extend(nestable.into_values().flatten());
+    thick
+}
+
+/// New labels for the blank nodes nested with `--thick`: `_:thick1`, `_:thick2`, and so on,
+/// skipping any label that the stored statements already use.
+struct ThickLabels {
+    used: BTreeSet<String>,
+    count: usize,
+}
+
+impl ThickLabels {
+    fn new(rows: &[Vec<Option<String>>]) -> ThickLabels {
+        let used = rows.iter()
+            .flat_map(|row| vec![&row[0], &row[1], &row[3]])
+            .filter_map(|cell| cell.as_ref())
+            .filter(|cell| cell.starts_with("_:"))
+            .cloned()
+            .collect();
+        ThickLabels { used, count: 0 }
+    }
+
+    fn next(&mut self) -> String {
+        loop {
+            self.count += 1;
+            let label = format!("_:thick{}", self.count);
+            if !self.used.contains(&label) {
+                return label;
+            }
+        }
+    }
+}
+
+/// Add rows to `out` for the statements about `subject` in JSON from `thicken`,
+/// with new labels for the nested blank nodes.
+fn flatten_json(
+    stanza: &Option<String>,
+    subject: &str,
+    json: &SerdeValue,
+    out: &mut Vec<Vec<Option<String>>>,
+    labels: &mut ThickLabels,
+) -> Result<(), Box<dyn Error>> {
+    let predicates = json.as_object().ok_or_else(|| format!("expected a JSON object for {}: {}", subject, json))?;
+    for (predicate, cells) in predicates.iter() {
+        for cell in cells.as_array().into_iter().flatten() {
+            let text = |key: &str| cell.get(key).and_then(|v| v.as_str()).map(String::from);
+            let mut row = vec![stanza.clone(), Some(subject.to_string()), Some(predicate.to_string()), None, None, None, None];
+            match cell.get("object") {
+                Some(nested) if nested.is_object() => {
+                    let label = labels.next();
+                    row[3] = Some(label.clone());
+                    out.push(row);
+                    flatten_json(stanza, &label, nested, out, labels)?;
+                    continue;
+                },
+                Some(_) => row[3] = text("object"),
+                None => {
+                    row[4] = text("value");
+                    row[5] = text("datatype");
+                    row[6] = text("language");
+                },
+            }
+            out.push(row);
+        }
+    }
+    Ok(())
+}
+
+/// Turn the JSON objects stored with `--thick` back into statements about blank nodes.
+fn unthicken(rows: Vec<Vec<Option<String>>>) -> Result<Vec<Vec<Option<String>>>, Box<dyn Error>> {
+    let mut flat = Vec::new();
+    let mut labels = ThickLabels::new(&rows);
+    for mut row in rows {
+        match row[3] {
+            Some(ref object) if object.starts_with('{') => {
+                let json: SerdeValue = serde_json::from_str(object)?;
+                let label = labels.next();
+                row[3] = Some(label.clone());
+                let stanza = row[0].clone();
+                flat.push(row);
+                flatten_json(&stanza, &label, &json, &mut flat, &mut labels)?;
+            },
+            _ => flat.push(row),
+        }
+    }
+    Ok(flat)
+}
+
+fn turtle_escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n").replace('\r', "\\r")
+}
+
+/// Write a value as a Turtle long string, keeping its line breaks, for XML literals.
+fn turtle_long_string(value: &str) -> String {
+    format!("\"\"\"{}\"\"\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Expand a stored subject, predicate, or object to a full IRI, keeping blank node labels.
+fn expand_node(prefixes: &[Prefix], term: &str) -> Result<String, Box<dyn Error>> {
+    if term.starts_with("_:") {
+        return Ok(term.to_string());
+    }
+    full_iri(prefixes, term).ok_or_else(|| format!("'{}' is not an IRI or a prefixed name", term).into())
+}
+
+/// Write a stored term as Turtle, in angle brackets unless it's a prefixed name or a blank node.
+fn turtle_term(prefixes: &[Prefix], term: &str) -> String {
+    if term.starts_with("_:") || term.starts_with('<') {
+        return term.to_string();
+    }
+    match term.find(':') {
+        Some(colon) if prefixes.iter().any(|p| p.prefix == term[..colon]) => term.to_string(),
+        _ => format!("<{}>", term),
+    }
+}
+
+fn to_subject(term: &str) -> NamedOrBlankNode<'_> {
+    match term.strip_prefix("_:") {
+        Some(id) => BlankNode { id }.into(),
+        None => NamedNode { iri: term }.into(),
+    }
+}
+
+/// Rebuild the RDF triple for each statement row and pass it to `on_triple`.
+fn statement_triples(
+    prefixes: &[Prefix],
+    rows: &[Vec<Option<String>>],
+    on_triple: &mut impl FnMut(Triple) -> Result<(), Box<dyn Error>>,
+) -> Result<(), Box<dyn Error>> {
+    for row in rows {
+        let (subject, predicate) = match (&row[1], &row[2]) {
+            (Some(subject), Some(predicate)) => (expand_node(prefixes, subject)?, expand_node(prefixes, predicate)?),
+            _ => return Err(format!("statement without a subject or predicate: {:?}", row).into()),
+        };
+        let object = match row[3] {
+            Some(ref object) => Some(expand_node(prefixes, object)?),
+            None => None,
+        };
+        let datatype = match row[5] {
+            Some(ref datatype) => Some(expand_node(prefixes, datatype)?),
+            None => None,
+        };
+        let object = match (&object, &row[4], &datatype, &row[6]) {
+            (Some(object), _, _, _) => Term::from(to_subject(object)),
+            (None, Some(value), Some(datatype), _) => Literal::Typed { value, datatype: NamedNode { iri: datatype } }.into(),
+            (None, Some(value), None, Some(language)) => Literal::LanguageTaggedString { value, language }.into(),
+            (None, Some(value), None, None) => Literal::Simple { value }.into(),
+            (None, None, _, _) => return Err(format!("statement without an object or value: {:?}", row).into()),
+        };
+        on_triple(Triple { subject: to_subject(&subject), predicate: NamedNode { iri: &predicate }, object })?;
+    }
+    Ok(())
+}
+
+/// Stream the statements table to STDOUT as CSV (or TSV, with a tab `delimiter`),
+/// with a header row. NULLs are empty cells.
+fn export_csv(conn: &Connection, delimiter: u8) -> Result<(), Box<dyn Error>> {
+    let stdout = io::stdout();
+    let mut writer = csv::WriterBuilder::new().delimiter(delimiter).from_writer(stdout.lock());
+    let mut columns = COLUMNS.to_vec();
+    if column_exists(conn, "statements", "graph")? {
+        columns.push("graph");
+    }
+    writer.write_record(&columns)?;
+    let mut stmt = conn.prepare(&format!("SELECT {} FROM statements ORDER BY {}", columns.join(", "), statement_order(conn)?))?;
+    let mut rows = stmt.query(params![])?;
+    while let Some(row) = rows.next()? {
+        let mut cells = Vec::new();
+        for i in 0..columns.len() {
+            cells.push(row.get::<_, Option<String>>(i)?.unwrap_or_default());
+        }
+        writer.write_record(&cells)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Print the statements table of a database as Turtle, N-Triples, RDFXML, CSV, or TSV.
+pub fn export(db: &str, format: &str) -> Result<(), Box<dyn Error>> {
+    if !["turtle", "ntriples", "rdfxml", "csv", "tsv"].contains(&format) {
+        return Err(RdftabError::Usage(format!("unknown export format '{}'", format)).into());
+    }
+    let mut conn = open_existing(db, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    if format == "csv" || format == "tsv" {
+        return export_csv(&conn, if format == "tsv" { b'\t' } else { b',' });
+    }
+    let prefixes = get_prefixes(&mut conn)?;
+    let rows = unthicken(read_statements(&conn)?)?;
+    let stdout = io::stdout();
+    let mut out = io::BufWriter::new(stdout.lock());
+    match format {
+        "turtle" => {
+            for prefix in &prefixes {
+                writeln!(out, "@prefix {}: <{}> .", prefix.prefix, prefix.base)?;
+            }
+            for row in &rows {
+                let cell = |i: usize| row[i].as_deref().unwrap_or("");
+                let object = match (&row[3], &row[5], &row[6]) {
+                    (Some(object), _, _) => turtle_term(&prefixes, object),
+                    (None, Some(datatype), _) if full_iri(&prefixes, datatype).as_deref() == Some(RDF_XML_LITERAL) => {
+                        format!("{}^^{}", turtle_long_string(cell(4)), turtle_term(&prefixes, datatype))
+                    },
+                    (None, Some(datatype), _) => format!("\"{}\"^^{}", turtle_escape(cell(4)), turtle_term(&prefixes, datatype)),
+                    (None, None, Some(language)) => format!("\"{}\"@{}", turtle_escape(cell(4)), language),
+                    (None, None, None) => format!("\"{}\"", turtle_escape(cell(4))),
+                };
+                writeln!(out, "{} {} {} .", turtle_term(&prefixes, cell(1)), turtle_term(&prefixes, cell(2)), object)?;
+            }
+        },
+        "ntriples" => {
+            statement_triples(&prefixes, &rows, &mut |triple| {
+                writeln!(out, "{} .", triple)?;
+                Ok(())
+            })?;
+        },
+        _ => {
+            let mut formatter = RdfXmlFormatter::new(out)?;
+            statement_triples(&prefixes, &rows, &mut |triple| {
+                formatter.format(&triple)?;
+                Ok(())
+            })?;
+            out = formatter.finish()?;
+        },
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// The N-Triples statements of a database, grouped by stanza.
+type Stanzas = BTreeMap<String, BTreeSet<String>>;
+
+/// Describe a blank node by its statements, recursively, so that blank nodes
+/// with the same structure get the same description whatever their labels.
+/// `path` holds the blank nodes we're already inside, to stop at cycles and at `MAX_NESTING`.
+fn blank_signature(
+    node: &str,
+    edges: &BTreeMap<String, Vec<(String, String)>>,
+    path: &mut Vec<String>,
+) -> String {
+    if path.len() >= MAX_NESTING || path.iter().any(|n| n == node) {
+        return "_:".to_string();
+    }
+    path.push(node.to_string());
+    let mut parts: Vec<String> = edges.get(node).into_iter().flatten()
+        .map(|(predicate, object)| if object.starts_with("_:") {
+            format!("{} [{}]", predicate, blank_signature(object, edges, path))
+        } else {
+            format!("{} {}", predicate, object)
+        })
+        .collect();
+    path.pop();
+    parts.sort();
+    parts.join("; ")
+}
+
+/// A 64-bit FNV-1a hash: unlike `DefaultHasher` it is fixed, so labels don't change with the Rust version.
+fn fnv1a(text: &str) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in text.bytes() {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
+
+/// A new blank node label from a hash of `key`, numbered if another node already has it.
+fn hashed_label(key: &str, taken: &mut BTreeSet<String>) -> String {
+    let hashed = format!("_:c{:016x}", fnv1a(key));
+    let mut label = hashed.clone();
+    let mut n = 1;
+    while !taken.insert(label.clone()) {
+        n += 1;
+        label = format!("{}-{}", hashed, n);
+    }
+    label
+}
+
+/// Give each blank node in `statements` a label that depends on its structure and where it's used,
+/// not on its stored label.
+/// A blank node that isn't the object of a statement about another blank node is labelled from
+/// its `blank_signature` and the statements that use it, and those inside it from their parent's label,
+/// so two nodes with the same structure keep their own labels, and nested nodes stay with their parents.
+/// Nodes that are still the same after that can't be told apart, so it doesn't matter which is which.
+fn canonical_labels(statements: &[(Option<String>, String, String, String)]) -> BTreeMap<String, String> {
+    let mut edges: BTreeMap<String, Vec<(String, String)>> = BTreeMap::new();
+    let mut uses: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    let mut nested = BTreeSet::new();
+    let mut nodes = BTreeSet::new();
+    for (_, subject, predicate, object) in statements {
+        if subject.starts_with("_:") {
+            nodes.insert(subject.clone());
+            edges.entry(subject.clone()).or_default().push((predicate.clone(), object.clone()));
+        }
+        if object.starts_with("_:") {
+            nodes.insert(object.clone());
+            if subject.starts_with("_:") {
+                nested.insert(object.clone());
+            } else {
+                uses.entry(object.clone()).or_default().push(format!("{} {}", subject, predicate));
+            }
+        }
+    }
+    let signatures: BTreeMap<&String, String> = nodes.iter()
+        .map(|node| (node, blank_signature(node, &edges, &mut Vec::new())))
+        .collect();
+
+    let mut roots: Vec<(String, &String)> = nodes.iter()
+        .filter(|node| !nested.contains(*node))
+        .map(|node| {
+            let mut used_by = uses.get(node).cloned().unwrap_or_default();
+            used_by.sort();
+            (format!("{} [{}]", used_by.join("; "), signatures[node]), node)
+        })
+        .collect();
+    roots.sort();
+    let mut labels: BTreeMap<String, String> = BTreeMap::new();
+    let mut taken = BTreeSet::new();
+    let mut queue = Vec::new();
+    for (key, node) in roots {
+        labels.insert(node.clone(), hashed_label(&key, &mut taken));
+        queue.push(node.clone());
+    }
+    loop {
+        while let Some(parent) = queue.pop() {
+            let mut children: Vec<(String, &String)> = edges.get(&parent).into_iter().flatten()
+                .filter(|(_, object)| object.starts_with("_:") && !labels.contains_key(object))
+                .map(|(predicate, object)| (format!("{} {} [{}]", labels[&parent], predicate, signatures[object]), object))
+                .collect();
+            children.sort();
+            for (key, child) in children {
+                if !labels.contains_key(child) {
+                    labels.insert(child.clone(), hashed_label(&key, &mut taken));
+                    queue.push(child.clone());
+                }
+            }
+        }
+        // Blank nodes that are only reachable from each other, in a cycle.
+        let mut rest: Vec<(&String, &String)> = nodes.iter()
+            .filter(|node| !labels.contains_key(*node))
+            .map(|node| (&signatures[node], node))
+            .collect();
+        rest.sort();
+        match rest.first() {
+            Some((signature, node)) => {
+                labels.insert(node.to_string(), hashed_label(signature, &mut taken));
+                queue.push(node.to_string());
+            },
+            None => break,
+        }
+    }
+    labels
+}
+
+/// Read the statements of a database as N-Triples, expanding prefixed names with its own prefix table,
+/// and relabelling each blank node with its `canonical_labels`.
+fn canonical_stanzas(db: &str) -> Result<Stanzas, Box<dyn Error>> {
+    let mut conn = open_existing(db, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let prefixes = get_prefixes(&mut conn)?;
+    let mut statements = Vec::new();
+    for row in unthicken(read_statements(&conn)?)? {
+        // Statements loaded from CSV may not have a stanza.
+        let stanza = match row[0] {
+            Some(ref stanza) => Some(expand_node(&prefixes, stanza)?),
+            None => None,
+        };
+        statement_triples(&prefixes, std::slice::from_ref(&row), &mut |triple| {
+            statements.push((stanza.clone(), triple.subject.to_string(), triple.predicate.to_string(), triple.object.to_string()));
+            Ok(())
+        })?;
+    }
+    let labels = canonical_labels(&statements);
+    let relabel = |node: &str| -> String { labels.get(node).cloned().unwrap_or_else(|| node.to_string()) };
+    let mut stanzas = Stanzas::new();
+    for (stanza, subject, predicate, object) in &statements {
+        let line = format!("{} {} {} .", relabel(subject), predicate, relabel(object));
+        let stanza = match stanza {
+            Some(stanza) if stanza.starts_with("_:") => relabel(stanza),
+            Some(stanza) => format!("<{}>", stanza),
+            None => relabel(subject),
+        };
+        stanzas.entry(stanza).or_default().insert(line);
+    }
+    Ok(stanzas)
+}
+
+/// Print the statements that are only in `old` (marked `-`) or only in `new` (marked `+`),
+/// grouped by stanza, as N-Triples.
+/// Prefixed names are expanded with each database's own prefixes before comparing,
+/// and blank nodes are compared by their structure rather than their labels.
+pub fn diff(old: &str, new: &str) -> Result<(), Box<dyn Error>> {
+    let old_stanzas = canonical_stanzas(old)?;
+    let new_stanzas = canonical_stanzas(new)?;
+    let empty = BTreeSet::new();
+    let keys: BTreeSet<&String> = old_stanzas.keys().chain(new_stanzas.keys()).collect();
+    let stdout = io::stdout();
+    let mut out = io::BufWriter::new(stdout.lock());
+    let mut removed = 0;
+    let mut added = 0;
+    for key in keys {
+        let before = old_stanzas.get(key).unwrap_or(&empty);
+        let after = new_stanzas.get(key).unwrap_or(&empty);
+        if before == after {
+            continue;
+        }
+        writeln!(out, "{}", key)?;
+        for line in before.difference(after) {
+            writeln!(out, "- {}", line)?;
+            removed += 1;
+        }
+        for line in after.difference(before) {
+            writeln!(out, "+ {}", line)?;
+            added += 1;
+        }
+        writeln!(out)?;
+    }
+    out.flush()?;
+    info!("{} statements removed, {} added", removed, added);
+    Ok(())
+}
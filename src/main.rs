@@ -1,119 +1,361 @@
 // Based on https://docs.rs/csv/1.1.3/csv/tutorial/index.html
-use std::error::Error;
 use std::env;
-use std::io;
 use std::process;
 
-use rio_xml::{RdfXmlParser, RdfXmlError};
-use rio_api::parser::TriplesParser;
-use rio_api::model::*;
+use clap::{crate_version, value_t, App, AppSettings, Arg, ArgMatches, SubCommand};
+use log::LevelFilter;
 
-use rusqlite::{params, Connection, Result};
+use rdftab::{count, diff, drop_graph, exit_code, export, graph, insert, merge, prefixes, reshorten, Options, DEFAULT_BATCH_SIZE, DEFAULT_BUSY_TIMEOUT_MS};
 
-#[derive(Debug)]
-struct Prefix {
-    prefix: String,
-    base: String
+fn positive_number(value: String) -> Result<(), String> {
+    match value.parse::<usize>() {
+        Ok(n) if n > 0 => Ok(()),
+        _ => Err(format!("expected a positive number, got '{}'", value)),
+    }
 }
 
-fn get_prefixes(conn: &mut Connection) -> Result<Vec<Prefix>> {
-    let mut stmt = conn.prepare("SELECT prefix, base FROM prefix ORDER BY length(base) DESC")?;
-    let mut rows = stmt.query(params![])?;
-    let mut prefixes = Vec::new();
-    while let Some(row) = rows.next()? {
-        prefixes.push(Prefix { prefix: row.get(0)?, base: row.get(1)? });
+fn number(value: String) -> Result<(), String> {
+    match value.parse::<u64>() {
+        Ok(_) => Ok(()),
+        _ => Err(format!("expected a number, got '{}'", value)),
     }
-    Ok(prefixes)
 }
 
-fn shorten(prefixes: &Vec<Prefix>, iri: &str) -> String {
-    for prefix in prefixes {
-        if iri.starts_with(&prefix.base) {
-            return iri.replace(&prefix.base, format!("{}:", prefix.prefix).as_str());
-        }
+/// Whether a global flag was given here or after the subcommand.
+fn given(matches: &ArgMatches, flag: &str) -> bool {
+    matches.is_present(flag) || matches.subcommand().1.is_some_and(|sub| sub.is_present(flag))
+}
+
+/// Log to STDERR at the info level, or as RUST_LOG says,
+/// unless --quiet or --verbose was given here or after the subcommand.
+fn init_logging(matches: &ArgMatches) {
+    let given = |flag| given(matches, flag);
+    let mut builder = env_logger::Builder::new();
+    builder.filter_level(LevelFilter::Info).format_timestamp(None).parse_default_env();
+    if given("quiet") {
+        builder.filter_level(LevelFilter::Error);
+    } else if given("verbose") {
+        builder.filter_level(LevelFilter::Debug);
     }
-    return format!("<{}>", iri);
+    builder.init();
 }
 
-fn insert(db: &String) -> Result<(), Box<dyn Error>> {
-    let stanza_end = NamedOrBlankNode::from(NamedNode { iri: "http://example.com/stanza-end" }).into();
-    let annotated_source = NamedNode { iri: "http://www.w3.org/2002/07/owl#annotatedSource" };
-    let reified_source = NamedNode { iri: "http://www.w3.org/1999/02/22-rdf-syntax-ns#subject" };
-    let stdin = io::stdin();
-    let mut stack: Vec<Vec<Option<String>>> = Vec::new();
-    let mut stanza = String::from("");
-    let mut conn = Connection::open(db)?;
-    let prefixes = get_prefixes(&mut conn).expect("Get prefixes");
-    let tx = conn.transaction()?;
-    tx.execute("CREATE TABLE IF NOT EXISTS statements (
-      stanza TEXT,
-      subject TEXT,
-      predicate TEXT,
-      object TEXT,
-      value TEXT,
-      datatype TEXT,
-      language TEXT
-    )", params![])?;
-    let filename = format!("file:{}", db);
-    RdfXmlParser::new(stdin.lock(), filename.as_str()).unwrap().parse_all(&mut |t| {
-        if t.subject == stanza_end {
-            while stack.len() > 0 {
-                if let Some(s) = stack.pop() {
-                    if stanza == "" {
-                        if let Some(ref sb) = s[0] {
-                            stanza = sb.clone();
-                        }
-                    }
-                    let mut v = vec![Some(stanza.to_string())];
-                    v.extend_from_slice(&s);
-                    let mut stmt = tx.prepare_cached("INSERT INTO statements values (?1, ?2, ?3, ?4, ?5, ?6, ?7)").expect("Statement ok");
-                    stmt.execute(v).expect("Insert row");
-                }
-            }
-            stanza = String::from("")
-        } else {
-            let subject = match t.subject {
-                NamedOrBlankNode::NamedNode(node) => Some(shorten(&prefixes, node.iri)),
-                NamedOrBlankNode::BlankNode(node) => Some(format!("_:{}", node.id)),
-            };
-            let predicate = Some(shorten(&prefixes, t.predicate.iri));
-            let (object, value, datatype, language) = match t.object {
-                Term::NamedNode(node) => (Some(shorten(&prefixes, node.iri)), None, None, None),
-                Term::BlankNode(node) => (Some(format!("_:{}", node.id)), None, None, None),
-                Term::Literal(node) => match node {
-                    Literal::Simple { value } => (None, Some(value.to_string()), None, None),
-                    Literal::Typed { value, datatype } => (None, Some(value.to_string()), Some(shorten(&prefixes, datatype.iri)), None),
-                    Literal::LanguageTaggedString { value, language } => (None, Some(value.to_string()), None, Some(language.to_string())),
-                },
-            };
-            stack.push(vec![subject, predicate, object, value, datatype, language]);
+/// The DB argument, or else the RDFTAB_DB environment variable.
+fn db_from(matches: &ArgMatches) -> String {
+    let env_db = || env::var("RDFTAB_DB").ok().filter(|db| !db.is_empty());
+    match matches.value_of("DB").map(String::from).or_else(env_db) {
+        Some(db) => db,
+        None => {
+            eprintln!("error: no DB given, and RDFTAB_DB is not set\n\n{}", matches.usage());
+            process::exit(2);
+        },
+    }
+}
 
-            match t.subject {
-                NamedOrBlankNode::NamedNode(node) => { stanza = shorten(&prefixes, node.iri); }
-                _ => { }
+fn main() {
+    let db = Arg::with_name("DB")
+        .help("The SQLite database file (default: $RDFTAB_DB)");
+    let matches = App::new("rdftab")
+        .version(crate_version!())
+        .about("Read RDFXML from STDIN into the statements table of a SQLite database")
+        .setting(AppSettings::SubcommandsNegateReqs)
+        .setting(AppSettings::VersionlessSubcommands)
+        .arg(Arg::with_name("quiet")
+            .long("quiet")
+            .short("q")
+            .global(true)
+            .help("Only print errors"))
+        .arg(Arg::with_name("verbose")
+            .long("verbose")
+            .short("v")
+            .global(true)
+            .conflicts_with("quiet")
+            .help("Print progress details"))
+        .arg(Arg::with_name("strict")
+            .long("strict")
+            .global(true)
+            .help("Stop with an error instead of printing a warning"))
+        .arg(Arg::with_name("format")
+            .long("format")
+            .takes_value(true)
+            .value_name("FORMAT")
+            .possible_values(&["rdfxml", "csv", "tsv"])
+            .default_value("rdfxml")
+            .help("Read RDFXML, or statements from CSV or TSV with a header row"))
+        .arg(Arg::with_name("prefixes")
+            .long("prefixes")
+            .takes_value(true)
+            .value_name("FILE")
+            .help("Load prefixes from a Turtle, CSV, or TSV file before parsing"))
+        .arg(Arg::with_name("prefixes-json")
+            .long("prefixes-json")
+            .takes_value(true)
+            .value_name("FILE")
+            .help("Load prefixes from a JSON object (such as a JSON-LD context) before parsing"))
+        .arg(Arg::with_name("no-auto-prefix")
+            .long("no-auto-prefix")
+            .help("Do not fill an empty prefix table from xmlns declarations"))
+        .arg(Arg::with_name("no-default-prefixes")
+            .long("no-default-prefixes")
+            .help("Do not fill an empty prefix table with the standard prefixes"))
+        .arg(Arg::with_name("base")
+            .long("base")
+            .takes_value(true)
+            .value_name("IRI")
+            .help("Resolve relative IRIs against IRI, unless the document has an xml:base"))
+        .arg(Arg::with_name("no-shorten")
+            .long("no-shorten")
+            .help("Store full IRIs instead of prefixed names"))
+        .arg(Arg::with_name("thick")
+            .long("thick")
+            .help("Store nested blank nodes as JSON in the object column"))
+        .arg(Arg::with_name("graph")
+            .long("graph")
+            .takes_value(true)
+            .value_name("IRI")
+            .help("Put the statements in the named graph IRI, in a graph column"))
+        .arg(Arg::with_name("batch-size")
+            .long("batch-size")
+            .takes_value(true)
+            .value_name("N")
+            .validator(positive_number)
+            .help("Insert up to N rows per INSERT statement, fewer if SQLite's parameter limit needs it (default 500)"))
+        .arg(Arg::with_name("commit-every")
+            .long("commit-every")
+            .takes_value(true)
+            .value_name("N")
+            .validator(positive_number)
+            .help("Commit after every N rows, keeping partial loads if one fails"))
+        .arg(Arg::with_name("limit")
+            .long("limit")
+            .takes_value(true)
+            .value_name("N")
+            .validator(positive_number)
+            .help("Stop after the first N stanzas"))
+        .arg(Arg::with_name("subject-prefix")
+            .long("subject-prefix")
+            .takes_value(true)
+            .value_name("PREFIX")
+            .help("Only load statements about subjects starting with this CURIE or IRI"))
+        .arg(Arg::with_name("exclude-predicate")
+            .long("exclude-predicate")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1)
+            .value_name("PREDICATE")
+            .help("Skip statements with this predicate (may be repeated)"))
+        .arg(Arg::with_name("validate-iris")
+            .long("validate-iris")
+            .help("Warn about malformed IRIs (or stop, with --strict)"))
+        .arg(Arg::with_name("dedup")
+            .long("dedup")
+            .help("Skip statements identical to one already inserted"))
+        .arg(Arg::with_name("replace")
+            .long("replace")
+            .help("Drop any existing statements table first"))
+        .arg(Arg::with_name("dry-run")
+            .long("dry-run")
+            .help("Parse and count statements without writing to the database"))
+        .arg(Arg::with_name("canonicalize-literals")
+            .long("canonicalize-literals")
+            .help("Store integer, decimal, boolean, and dateTime values in canonical form"))
+        .arg(Arg::with_name("trim-literals")
+            .long("trim-literals")
+            .help("Remove leading and trailing whitespace from literal values, including xsd:string values"))
+        .arg(Arg::with_name("normalize-lang")
+            .long("normalize-lang")
+            .help("Store language tags in lowercase, so that en-US and EN-us are the same"))
+        .arg(Arg::with_name("emit-kind")
+            .long("emit-kind")
+            .help("Say whether each object is an iri, a bnode, or a literal in a kind column"))
+        .arg(Arg::with_name("preserve-order")
+            .long("preserve-order")
+            .help("Number statements in input order in a seq column"))
+        .arg(Arg::with_name("json")
+            .long("json")
+            .help("Also print each statement to STDOUT as a line of JSON"))
+        .arg(Arg::with_name("stats-json")
+            .long("stats-json")
+            .takes_value(true)
+            .value_name("FILE")
+            .help("Write the import statistics to FILE as JSON"))
+        .arg(Arg::with_name("no-metadata")
+            .long("no-metadata")
+            .help("Do not record this import in the import_metadata table"))
+        .arg(Arg::with_name("fast")
+            .long("fast")
+            .help("Use faster but less durable SQLite settings for bulk loading"))
+        .arg(Arg::with_name("busy-timeout-ms")
+            .long("busy-timeout-ms")
+            .takes_value(true)
+            .value_name("MS")
+            .validator(number)
+            .help("Keep retrying for MS milliseconds while the database is locked (default 5000)"))
+        .arg(Arg::with_name("bnode-prefix")
+            .long("bnode-prefix")
+            .takes_value(true)
+            .value_name("PREFIX")
+            .help("Label blank nodes _:PREFIX... to keep separate runs apart"))
+        .arg(Arg::with_name("in-memory")
+            .long("in-memory")
+            .help("Build the database in memory instead of a file (the same as DB ':memory:'), reading every argument as an INPUT"))
+        .arg(Arg::with_name("save")
+            .long("save")
+            .takes_value(true)
+            .value_name("FILE")
+            .conflicts_with("dry-run")
+            .help("Copy the finished database to FILE"))
+        .arg(db.clone())
+        .arg(Arg::with_name("INPUT")
+            .multiple(true)
+            .help("RDFXML files (or, with the url feature, HTTP(S) URLs) to read instead of STDIN, loaded together"))
+        .subcommand(SubCommand::with_name("graph")
+            .about("Print the rdfs:subClassOf hierarchy as a graph")
+            .arg(Arg::with_name("dot")
+                .long("dot")
+                .help("Print a DOT graph (the default)"))
+            .arg(Arg::with_name("mermaid")
+                .long("mermaid")
+                .conflicts_with("dot")
+                .help("Print a Mermaid diagram"))
+            .arg(db.clone()))
+        .subcommand(SubCommand::with_name("export")
+            .about("Print the statements table as RDF")
+            .arg(Arg::with_name("format")
+                .long("format")
+                .takes_value(true)
+                .value_name("FORMAT")
+                .possible_values(&["turtle", "ntriples", "rdfxml", "csv", "tsv"])
+                .default_value("turtle")
+                .help("The RDF syntax to print"))
+            .arg(db.clone()))
+        .subcommand(SubCommand::with_name("count")
+            .about("Print the number of triples in RDFXML files or STDIN, without loading them")
+            .arg(Arg::with_name("format")
+                .long("format")
+                .takes_value(true)
+                .value_name("FORMAT")
+                .possible_values(&["rdfxml", "csv", "tsv"])
+                .default_value("rdfxml")
+                .help("Read RDFXML, or count the rows of CSV or TSV with a header row"))
+            .arg(Arg::with_name("INPUT")
+                .multiple(true)
+                .help("RDFXML files to read instead of STDIN")))
+        .subcommand(SubCommand::with_name("diff")
+            .about("Print the statements that differ between two databases")
+            .arg(Arg::with_name("OLD")
+                .help("The SQLite database to compare from")
+                .required(true))
+            .arg(Arg::with_name("NEW")
+                .help("The SQLite database to compare to")
+                .required(true)))
+        .subcommand(SubCommand::with_name("merge")
+            .about("Copy the statements and prefixes of one database into another")
+            .arg(Arg::with_name("SOURCE")
+                .help("The SQLite database to copy from")
+                .required(true))
+            .arg(Arg::with_name("TARGET")
+                .help("The SQLite database to copy into")
+                .required(true)))
+        .subcommand(SubCommand::with_name("prefixes")
+            .about("Print the prefix table as Turtle @prefix lines")
+            .arg(db.clone()))
+        .subcommand(SubCommand::with_name("drop-graph")
+            .about("Delete the statements in one named graph")
+            .arg(Arg::with_name("graph")
+                .long("graph")
+                .takes_value(true)
+                .value_name("IRI")
+                .required_unless("default-graph")
+                .help("The graph to delete, as given to --graph when loading"))
+            .arg(Arg::with_name("default-graph")
+                .long("default-graph")
+                .conflicts_with("graph")
+                .help("Delete the statements that were loaded without --graph"))
+            .arg(db.clone()))
+        .subcommand(SubCommand::with_name("reshorten")
+            .about("Re-apply the prefix table to the statements table")
+            .arg(db))
+        .get_matches_safe()
+        .unwrap_or_else(|err| {
+            if err.use_stderr() {
+                eprintln!("{}", err.message);
+                process::exit(2);
             }
-            if stanza == "" && (t.predicate == annotated_source || t.predicate == reified_source) {
-                match t.object {
-                    Term::NamedNode(node) => { stanza = shorten(&prefixes, node.iri); },
-                    _ => { }
+            // --help and --version
+            println!("{}", err.message);
+            process::exit(0);
+        });
+
+    init_logging(&matches);
+    let result = match matches.subcommand() {
+        ("graph", Some(sub)) => {
+            let format = if sub.is_present("mermaid") { "mermaid" } else { "dot" };
+            graph(&db_from(sub), format)
+        },
+        ("export", Some(sub)) => export(&db_from(sub), sub.value_of("format").unwrap()),
+        ("count", Some(sub)) => {
+            let inputs: Vec<String> = sub.values_of("INPUT").map(|v| v.map(String::from).collect()).unwrap_or_default();
+            count(&inputs, sub.value_of("format").unwrap())
+        },
+        ("diff", Some(sub)) => diff(sub.value_of("OLD").unwrap(), sub.value_of("NEW").unwrap()),
+        ("merge", Some(sub)) => merge(sub.value_of("SOURCE").unwrap(), sub.value_of("TARGET").unwrap(), given(&matches, "strict")),
+        ("prefixes", Some(sub)) => prefixes(&db_from(sub)),
+        ("drop-graph", Some(sub)) => drop_graph(&db_from(sub), sub.value_of("graph")),
+        ("reshorten", Some(sub)) => reshorten(&db_from(sub)),
+        _ => {
+            let mut inputs: Vec<String> = matches.values_of("INPUT").map(|v| v.map(String::from).collect()).unwrap_or_default();
+            if matches.is_present("in-memory") {
+                // There's no DB, so the first positional argument is an input too.
+                if let Some(first) = matches.value_of("DB") {
+                    inputs.insert(0, first.to_string());
                 }
             }
-        }
-        Ok(()) as Result<(), RdfXmlError>
-    }).unwrap();
-    tx.commit()?;
-    Ok(())
-}
-
-fn main() {
-    let args: Vec<String> = env::args().collect();
-    if args.len() != 2 {
-        println!("Usage: rdftab target.db");
-        process::exit(1);
-    }
-    let db = &args[1];
-    if let Err(err) = insert(db) {
-        println!("{}", err);
-        process::exit(1);
+            let options = Options {
+                format: matches.value_of("format").unwrap().to_string(),
+                inputs,
+                prefixes: matches.value_of("prefixes").map(String::from),
+                prefixes_json: matches.value_of("prefixes-json").map(String::from),
+                no_auto_prefix: matches.is_present("no-auto-prefix"),
+                no_default_prefixes: matches.is_present("no-default-prefixes"),
+                // The validator has already rejected bad values.
+                batch_size: value_t!(matches, "batch-size", usize).unwrap_or(DEFAULT_BATCH_SIZE),
+                dedup: matches.is_present("dedup"),
+                replace: matches.is_present("replace"),
+                dry_run: matches.is_present("dry-run"),
+                bnode_prefix: matches.value_of("bnode-prefix").unwrap_or("").to_string(),
+                fast: matches.is_present("fast"),
+                busy_timeout_ms: value_t!(matches, "busy-timeout-ms", u64).unwrap_or(DEFAULT_BUSY_TIMEOUT_MS),
+                save: matches.value_of("save").map(String::from),
+                canonicalize_literals: matches.is_present("canonicalize-literals"),
+                preserve_order: matches.is_present("preserve-order"),
+                json: matches.is_present("json"),
+                limit: value_t!(matches, "limit", usize).ok(),
+                subject_prefix: matches.value_of("subject-prefix").map(String::from),
+                exclude_predicates: matches.values_of("exclude-predicate").map(|v| v.map(String::from).collect()).unwrap_or_default(),
+                no_metadata: matches.is_present("no-metadata"),
+                validate_iris: matches.is_present("validate-iris"),
+                base: matches.value_of("base").map(String::from),
+                trim_literals: matches.is_present("trim-literals"),
+                normalize_lang: matches.is_present("normalize-lang"),
+                commit_every: value_t!(matches, "commit-every", usize).ok(),
+                emit_kind: matches.is_present("emit-kind"),
+                no_shorten: matches.is_present("no-shorten"),
+                thick: matches.is_present("thick"),
+                graph: matches.value_of("graph").map(String::from),
+                stats_json: matches.value_of("stats-json").map(String::from),
+                strict: matches.is_present("strict"),
+                counters: Default::default(),
+            };
+            if matches.is_present("in-memory") {
+                insert(":memory:", &options)
+            } else {
+                insert(&db_from(&matches), &options)
+            }
+        },
+    };
+    if let Err(err) = result {
+        eprintln!("{}", err);
+        process::exit(exit_code(err.as_ref()));
     }
 }
@@ -4,21 +4,44 @@ WITH literal(value, escaped) AS (
     replace(replace(replace(value, '\', '\\'), '"', '\"'), '
 ', '\n') AS escaped
   FROM statements
+),
+term(value, turtle) AS (
+  SELECT DISTINCT
+    value,
+    CASE
+      WHEN substr(value, 1, 2) = '_:' THEN value
+      WHEN EXISTS (
+        SELECT 1 FROM prefix
+        WHERE substr(value, 1, length(coalesce(prefix, '')) + 1) = coalesce(prefix, '') || ':'
+      ) THEN value
+      ELSE '<' || value || '>'
+    END AS turtle
+  FROM (
+    SELECT subject AS value FROM statements
+    UNION SELECT predicate FROM statements
+    UNION SELECT object FROM statements
+    UNION SELECT datatype FROM statements
+  )
 )
 SELECT
-  "@prefix " || prefix || ": <" || base || "> ."
+  "@prefix " || coalesce(prefix, '') || ": <" || base || "> ."
 FROM prefix
 UNION ALL
 SELECT 
-   subject
+   s.turtle
 || " "
-|| predicate
+|| p.turtle
 || " "
 || coalesce(
-     object,
-     """" || escaped || """^^" || datatype,
+     o.turtle,
+     """" || escaped || """^^" || d.turtle,
      """" || escaped || """@" || language,
      """" || escaped || """"
    )
 || " ."
-FROM statements LEFT JOIN literal ON statements.value = literal.value;
+FROM statements
+LEFT JOIN literal ON statements.value = literal.value
+LEFT JOIN term AS s ON statements.subject = s.value
+LEFT JOIN term AS p ON statements.predicate = p.value
+LEFT JOIN term AS o ON statements.object = o.value
+LEFT JOIN term AS d ON statements.datatype = d.value;
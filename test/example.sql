@@ -26,8 +26,8 @@ INSERT INTO statements VALUES('ex:foo','ex:foo','rdfs:subClassOf','_:b1',NULL,NU
 INSERT INTO statements VALUES('ex:foo','_:b1','owl:someValuesFrom','ex:bar',NULL,NULL,NULL);
 INSERT INTO statements VALUES('ex:foo','_:b1','owl:onProperty','ex:part-of',NULL,NULL,NULL);
 INSERT INTO statements VALUES('ex:foo','_:b1','rdf:type','owl:Restriction',NULL,NULL,NULL);
-INSERT INTO statements VALUES('ex:foo','ex:foo','<ex:link>','<http://exaple.com/foo>',NULL,NULL,NULL);
-INSERT INTO statements VALUES('ex:foo','ex:foo','<ex:size>',NULL,'123','xsd:int',NULL);
+INSERT INTO statements VALUES('ex:foo','ex:foo','ex:link','http://exaple.com/foo',NULL,NULL,NULL);
+INSERT INTO statements VALUES('ex:foo','ex:foo','ex:size',NULL,'123','xsd:int',NULL);
 INSERT INTO statements VALUES('ex:foo','ex:foo','rdfs:label',NULL,'Fou',NULL,'fr');
 INSERT INTO statements VALUES('ex:foo','ex:foo','rdfs:label',NULL,'Foo',NULL,NULL);
 INSERT INTO statements VALUES('ex:foo','ex:foo','rdf:type','owl:Class',NULL,NULL,NULL);
@@ -0,0 +1,1282 @@
+//! Load the RDFXML fixtures in `test/` and compare the statement rows,
+//! and the N-Triples from `rdftab export`, with the expected results.
+//! Blank node labels are replaced by `_:x`, and rows are sorted, before comparing.
+use std::collections::BTreeSet;
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::Ordering;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use rusqlite::{params, Connection};
+
+use rdftab::{
+    canonicalize_literal, create_prefix_table, create_statements_table, default_prefixes, exit_code, expand,
+    get_prefixes, load_csv, rdf_to_rows, read_namespaces, read_prefix_file, read_prefix_json, shorten, store_prefixes,
+    subject_to_json, Options, Prefix, RdftabError, SqliteSink, MAX_NESTING,
+};
+
+fn prefixes() -> Vec<Prefix> {
+    let mut prefixes = default_prefixes();
+    prefixes.push(Prefix { prefix: "ex".to_string(), base: "http://example.com/".to_string() });
+    prefixes
+}
+
+fn fixture_path(name: &str) -> String {
+    format!("{}/test/{}", env!("CARGO_MANIFEST_DIR"), name)
+}
+
+fn fixture(name: &str) -> String {
+    fs::read_to_string(fixture_path(name)).unwrap()
+}
+
+fn normalize(cell: &str) -> String {
+    if cell.starts_with("_:") { "_:x".to_string() } else { cell.to_string() }
+}
+
+/// Parse a fixture into sorted rows, with NULLs as empty strings.
+fn rows(name: &str) -> Vec<Vec<String>> {
+    let mut rows: Vec<Vec<String>> = rdf_to_rows(&fixture(name), &prefixes()).unwrap().iter()
+        .map(|row| row.iter().map(|cell| normalize(cell.as_deref().unwrap_or(""))).collect())
+        .collect();
+    rows.sort();
+    rows
+}
+
+fn expected(rows: &[[&str; 7]]) -> Vec<Vec<String>> {
+    let mut rows: Vec<Vec<String>> = rows.iter().map(|row| row.iter().map(|cell| cell.to_string()).collect()).collect();
+    rows.sort();
+    rows
+}
+
+#[test]
+fn plain_and_literal_triples_and_blank_nodes() {
+    assert_eq!(rows("example.owl"), expected(&[
+        ["ex:foo", "ex:foo", "rdf:type", "owl:Class", "", "", ""],
+        ["ex:foo", "ex:foo", "rdfs:label", "", "Foo", "", ""],
+        ["ex:foo", "ex:foo", "rdfs:label", "", "Fou", "", "fr"],
+        ["ex:foo", "ex:foo", "ex:size", "", "123", "xsd:int", ""],
+        ["ex:foo", "ex:foo", "ex:link", "ex:foo", "", "", ""],
+        ["ex:foo", "ex:foo", "rdfs:subClassOf", "_:x", "", "", ""],
+        ["ex:foo", "_:x", "rdf:type", "owl:Restriction", "", "", ""],
+        ["ex:foo", "_:x", "owl:onProperty", "ex:part-of", "", "", ""],
+        ["ex:foo", "_:x", "owl:someValuesFrom", "ex:bar", "", "", ""],
+    ]));
+}
+
+#[test]
+fn blank_node_objects_are_the_subjects_of_their_statements() {
+    let rows = rdf_to_rows(&fixture("example.owl"), &prefixes()).unwrap();
+    let restriction = rows.iter()
+        .find(|row| row[2].as_deref() == Some("rdfs:subClassOf"))
+        .and_then(|row| row[3].clone())
+        .unwrap();
+    assert_eq!(rows.iter().filter(|row| row[1].as_ref() == Some(&restriction)).count(), 3);
+}
+
+#[test]
+fn annotation_axioms_go_with_their_source() {
+    assert_eq!(rows("nested-annotation.owl"), expected(&[
+        ["ex:foo", "ex:foo", "rdf:type", "owl:Class", "", "", ""],
+        ["ex:foo", "ex:foo", "rdfs:label", "", "Foo", "", ""],
+        ["ex:foo", "_:x", "rdf:type", "owl:Axiom", "", "", ""],
+        ["ex:foo", "_:x", "owl:annotatedSource", "ex:foo", "", "", ""],
+        ["ex:foo", "_:x", "owl:annotatedProperty", "rdfs:label", "", "", ""],
+        ["ex:foo", "_:x", "owl:annotatedTarget", "", "Foo", "", ""],
+        ["ex:foo", "_:x", "rdfs:comment", "", "A silly label", "", ""],
+        ["ex:foo", "_:x", "rdf:type", "owl:Axiom", "", "", ""],
+        ["ex:foo", "_:x", "owl:annotatedSource", "_:x", "", "", ""],
+        ["ex:foo", "_:x", "owl:annotatedProperty", "rdfs:comment", "", "", ""],
+        ["ex:foo", "_:x", "owl:annotatedTarget", "", "A silly label", "", ""],
+        ["ex:foo", "_:x", "rdfs:seeAlso", "ex:review", "", "", ""],
+    ]));
+}
+
+#[test]
+fn reified_statements_go_with_their_subject() {
+    assert_eq!(rows("reification.owl"), expected(&[
+        ["ex:foo", "ex:foo", "rdf:type", "owl:Class", "", "", ""],
+        ["ex:foo", "ex:foo", "rdfs:label", "", "Foo", "", ""],
+        ["ex:foo", "_:x", "rdf:type", "rdf:Statement", "", "", ""],
+        ["ex:foo", "_:x", "rdf:subject", "ex:foo", "", "", ""],
+        ["ex:foo", "_:x", "rdf:predicate", "rdfs:label", "", "", ""],
+        ["ex:foo", "_:x", "rdf:object", "", "Foo", "", ""],
+        ["ex:foo", "_:x", "ex:source", "ex:paper", "", "", ""],
+    ]));
+}
+
+#[test]
+fn collections_end_with_rdf_nil() {
+    assert_eq!(rows("empty-list.owl"), expected(&[
+        ["ex:empty", "ex:empty", "rdf:type", "owl:Class", "", "", ""],
+        ["ex:empty", "ex:empty", "owl:unionOf", "rdf:nil", "", "", ""],
+        ["ex:pair", "ex:pair", "rdf:type", "owl:Class", "", "", ""],
+        ["ex:pair", "ex:pair", "owl:unionOf", "_:x", "", "", ""],
+        ["ex:pair", "_:x", "rdf:first", "ex:a", "", "", ""],
+        ["ex:pair", "_:x", "rdf:rest", "_:x", "", "", ""],
+        ["ex:pair", "_:x", "rdf:first", "ex:b", "", "", ""],
+        ["ex:pair", "_:x", "rdf:rest", "rdf:nil", "", "", ""],
+    ]));
+}
+
+#[test]
+fn statements_about_the_stanza_end_iri_are_data() {
+    assert_eq!(rows("stanza-end.owl"), expected(&[
+        ["ex:stanza-end", "ex:stanza-end", "rdfs:label", "", "End", "", ""],
+        ["ex:stanza-end", "ex:stanza-end", "ex:next", "ex:foo", "", "", ""],
+        ["ex:foo", "ex:foo", "rdf:type", "owl:Class", "", "", ""],
+    ]));
+    assert_eq!(rdftab(&["count", &fixture_path("stanza-end.owl")], None).trim(), "3");
+}
+
+#[test]
+fn xml_literals_keep_their_datatype() {
+    let rows = rdf_to_rows(&fixture("xml-literal.owl"), &prefixes()).unwrap();
+    let comment = rows.iter().find(|row| row[2].as_deref() == Some("rdfs:comment")).unwrap();
+    assert_eq!(comment[5].as_deref(), Some("rdf:XMLLiteral"));
+    let value = comment[4].as_deref().unwrap();
+    assert!(value.contains("<em>"));
+
+    let db = temp_db("xml-literal");
+    let db = db.to_str().unwrap();
+    rdftab(&[db], Some("xml-literal.owl"));
+    let turtle = rdftab(&["export", "--format", "turtle", db], None);
+    let expected = format!(
+        "ex:foo rdfs:comment \"\"\"{}\"\"\"^^rdf:XMLLiteral .\n",
+        value.replace('\\', "\\\\").replace('"', "\\\""),
+    );
+    assert!(turtle.contains(&expected), "{}", turtle);
+    fs::remove_file(db).unwrap();
+}
+
+fn temp_db(name: &str) -> PathBuf {
+    let path = env::temp_dir().join(format!("rdftab-{}-{}.db", name, std::process::id()));
+    let _ = fs::remove_file(&path);
+    path
+}
+
+fn rdftab(args: &[&str], stdin: Option<&str>) -> String {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_rdftab"));
+    command.arg("--quiet").args(args);
+    if let Some(path) = stdin {
+        command.stdin(fs::File::open(fixture_path(path)).unwrap());
+    }
+    let output = command.output().unwrap();
+    assert!(output.status.success(), "rdftab {:?} failed: {}", args, String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn export_round_trip() {
+    let db = temp_db("round-trip");
+    let db = db.to_str().unwrap();
+    rdftab(&[db], Some("example.owl"));
+    let ntriples = rdftab(&["export", "--format", "ntriples", db], None);
+    let mut lines: Vec<String> = ntriples.lines()
+        .map(|line| line.split(' ').map(normalize).collect::<Vec<_>>().join(" "))
+        .collect();
+    lines.sort();
+    let mut expected = vec![
+        "<http://example.com/foo> <http://www.w3.org/1999/02/22-rdf-syntax-ns#type> <http://www.w3.org/2002/07/owl#Class> .",
+        "<http://example.com/foo> <http://www.w3.org/2000/01/rdf-schema#label> \"Foo\" .",
+        "<http://example.com/foo> <http://www.w3.org/2000/01/rdf-schema#label> \"Fou\"@fr .",
+        "<http://example.com/foo> <http://example.com/size> \"123\"^^<http://www.w3.org/2001/XMLSchema#int> .",
+        "<http://example.com/foo> <http://example.com/link> <http://example.com/foo> .",
+        "<http://example.com/foo> <http://www.w3.org/2000/01/rdf-schema#subClassOf> _:x .",
+        "_:x <http://www.w3.org/1999/02/22-rdf-syntax-ns#type> <http://www.w3.org/2002/07/owl#Restriction> .",
+        "_:x <http://www.w3.org/2002/07/owl#onProperty> <http://example.com/part-of> .",
+        "_:x <http://www.w3.org/2002/07/owl#someValuesFrom> <http://example.com/bar> .",
+    ];
+    expected.sort_unstable();
+    assert_eq!(lines, expected);
+    fs::remove_file(db).unwrap();
+}
+
+#[test]
+fn normalized_language_tags_are_duplicates() {
+    let db = temp_db("language-tags");
+    let db = db.to_str().unwrap();
+    rdftab(&["--normalize-lang", "--dedup", db, &fixture_path("language-tags.owl")], None);
+    let ntriples = rdftab(&["export", "--format", "ntriples", db], None);
+    assert_eq!(ntriples.lines().collect::<Vec<_>>(), vec![
+        "<http://example.com/color> <http://www.w3.org/2000/01/rdf-schema#label> \"Color\"@en-us .",
+    ]);
+    fs::remove_file(db).unwrap();
+}
+
+#[test]
+fn xsd_strings_are_plain_literals() {
+    assert_eq!(rows("xsd-string.owl"), expected(&[
+        ["ex:color", "ex:color", "rdfs:label", "", "Color", "", ""],
+        ["ex:color", "ex:color", "rdfs:label", "", "Color", "", ""],
+    ]));
+
+    let db = temp_db("xsd-string");
+    let db = db.to_str().unwrap();
+    rdftab(&["--dedup", db, &fixture_path("xsd-string.owl")], None);
+    let ntriples = rdftab(&["export", "--format", "ntriples", db], None);
+    assert_eq!(ntriples.lines().collect::<Vec<_>>(), vec![
+        "<http://example.com/color> <http://www.w3.org/2000/01/rdf-schema#label> \"Color\" .",
+    ]);
+    fs::remove_file(db).unwrap();
+}
+
+#[test]
+fn unprefixed_iris_are_stored_bare_and_exported_in_brackets() {
+    let db = temp_db("bare-iris");
+    let db = db.to_str().unwrap();
+    rdftab(&["--no-auto-prefix", db], Some("example.owl"));
+    let conn = Connection::open(db).unwrap();
+    let bracketed: i64 = conn.query_row(
+        "SELECT count(*) FROM statements
+         WHERE substr(subject, 1, 1) = '<' OR substr(predicate, 1, 1) = '<'
+            OR substr(object, 1, 1) = '<' OR substr(datatype, 1, 1) = '<'",
+        params![],
+        |row| row.get(0),
+    ).unwrap();
+    assert_eq!(bracketed, 0);
+    let object: String = conn.query_row(
+        "SELECT object FROM statements WHERE predicate = 'http://example.com/link'",
+        params![],
+        |row| row.get(0),
+    ).unwrap();
+    assert_eq!(object, "http://example.com/foo");
+
+    let turtle = rdftab(&["export", "--format", "turtle", db], None);
+    let lines: Vec<&str> = turtle.lines().collect();
+    assert!(lines.contains(&"<http://example.com/foo> <http://example.com/link> <http://example.com/foo> ."), "{}", turtle);
+    assert!(lines.contains(&"<http://example.com/foo> rdf:type owl:Class ."), "{}", turtle);
+    fs::remove_file(db).unwrap();
+}
+
+#[test]
+fn replace_with_a_graph_keeps_the_other_graphs() {
+    let db = temp_db("graphs");
+    let db = db.to_str().unwrap();
+    rdftab(&["--graph", "ex:a", db], Some("example.owl"));
+    rdftab(&["--graph", "ex:b", db], Some("language-tags.owl"));
+    rdftab(&["--graph", "ex:a", "--replace", db], Some("example.owl"));
+    let conn = Connection::open(db).unwrap();
+    let count = |graph: &str| -> usize {
+        conn.query_row("SELECT count(*) FROM statements WHERE graph = ?", params![graph], |row| row.get::<_, i64>(0))
+            .unwrap() as usize
+    };
+    assert_eq!(count("ex:a"), rdf_to_rows(&fixture("example.owl"), &prefixes()).unwrap().len());
+    assert_eq!(count("ex:b"), rdf_to_rows(&fixture("language-tags.owl"), &prefixes()).unwrap().len());
+    fs::remove_file(db).unwrap();
+}
+
+#[test]
+fn graphs_keep_their_blank_nodes_apart() {
+    let db = temp_db("graph-bnodes");
+    let db = db.to_str().unwrap();
+    rdftab(&["--graph", "ex:a", db], Some("example.owl"));
+    rdftab(&["--graph", "ex:b", db], Some("example.owl"));
+    let conn = Connection::open(db).unwrap();
+    let shared: i64 = conn.query_row(
+        "SELECT count(*) FROM statements AS a JOIN statements AS b ON a.subject = b.subject
+         WHERE a.graph = 'ex:a' AND b.graph = 'ex:b' AND substr(a.subject, 1, 2) = '_:'",
+        params![],
+        |row| row.get(0),
+    ).unwrap();
+    assert_eq!(shared, 0);
+    let csv = rdftab(&["export", "--format", "csv", db], None);
+    assert_eq!(csv.lines().next(), Some("stanza,subject,predicate,object,value,datatype,language,graph"));
+    fs::remove_file(db).unwrap();
+}
+
+#[test]
+fn drop_graph_deletes_one_graph() {
+    let db = temp_db("drop-graph");
+    let db = db.to_str().unwrap();
+    rdftab(&[db], Some("language-tags.owl"));
+    rdftab(&["--graph", "ex:a", db], Some("example.owl"));
+    rdftab(&["--graph", "ex:b", db], Some("example.owl"));
+    rdftab(&["drop-graph", "--graph", "ex:a", db], None);
+    let conn = Connection::open(db).unwrap();
+    let graphs: Vec<Option<String>> = {
+        let mut stmt = conn.prepare("SELECT DISTINCT graph FROM statements ORDER BY graph").unwrap();
+        let rows = stmt.query_map(params![], |row| row.get(0)).unwrap();
+        rows.map(|graph| graph.unwrap()).collect()
+    };
+    assert_eq!(graphs, vec![None, Some("ex:b".to_string())]);
+    fs::remove_file(db).unwrap();
+}
+
+#[test]
+fn canonical_dates_and_times_are_duplicates() {
+    let db = temp_db("date-times");
+    let db = db.to_str().unwrap();
+    rdftab(&["--canonicalize-literals", "--dedup", db, &fixture_path("date-times.owl")], None);
+    let ntriples = rdftab(&["export", "--format", "ntriples", db], None);
+    let mut lines: Vec<&str> = ntriples.lines().collect();
+    lines.sort_unstable();
+    assert_eq!(lines, vec![
+        "<http://example.com/launch> <http://example.com/at> \"2020-01-01T12:00:00Z\"^^<http://www.w3.org/2001/XMLSchema#dateTime> .",
+        "<http://example.com/launch> <http://example.com/from> \"12:00:00.5Z\"^^<http://www.w3.org/2001/XMLSchema#time> .",
+        "<http://example.com/launch> <http://example.com/from> \"soon\"^^<http://www.w3.org/2001/XMLSchema#time> .",
+        "<http://example.com/launch> <http://example.com/on> \"2020-01-01Z\"^^<http://www.w3.org/2001/XMLSchema#date> .",
+    ]);
+    fs::remove_file(db).unwrap();
+}
+
+#[test]
+fn canonical_numbers_and_booleans() {
+    let xsd = |local: &str| format!("http://www.w3.org/2001/XMLSchema#{}", local);
+    let canonical = |value: &str, local: &str| canonicalize_literal(value, &xsd(local));
+    assert_eq!(canonical("+01", "integer"), Ok("1".to_string()));
+    assert_eq!(canonical("-0", "integer"), Ok("0".to_string()));
+    assert_eq!(canonical(" 007 ", "int"), Ok("7".to_string()));
+    assert_eq!(canonical("1.", "decimal"), Ok("1.0".to_string()));
+    assert_eq!(canonical(".5", "decimal"), Ok("0.5".to_string()));
+    assert_eq!(canonical("-0.0", "decimal"), Ok("0.0".to_string()));
+    assert_eq!(canonical("+1.50", "decimal"), Ok("1.5".to_string()));
+    assert_eq!(canonical("1", "boolean"), Ok("true".to_string()));
+    assert_eq!(canonical("0", "boolean"), Ok("false".to_string()));
+    for (value, local) in &[("TRUE", "boolean"), ("1e3", "integer"), (".", "decimal"), ("", "integer")] {
+        assert!(canonical(value, local).is_err(), "{} {}", value, local);
+    }
+
+    // Bounded types check their range.
+    assert_eq!(canonical("127", "byte"), Ok("127".to_string()));
+    assert_eq!(canonical("-128", "byte"), Ok("-128".to_string()));
+    assert_eq!(canonical("18446744073709551615", "unsignedLong"), Ok("18446744073709551615".to_string()));
+    assert_eq!(canonical("-0", "nonNegativeInteger"), Ok("0".to_string()));
+    assert_eq!(canonical("123456789012345678901234567890", "integer"), Ok("123456789012345678901234567890".to_string()));
+    for (value, local) in &[
+        ("128", "byte"),
+        ("-129", "byte"),
+        ("300", "unsignedByte"),
+        ("-1", "unsignedInt"),
+        ("2147483648", "int"),
+        ("18446744073709551616", "unsignedLong"),
+        ("0", "positiveInteger"),
+        ("1", "negativeInteger"),
+    ] {
+        assert!(canonical(value, local).is_err(), "{} {}", value, local);
+    }
+}
+
+#[test]
+fn invalid_literals_are_kept_with_a_warning() {
+    let db = temp_db("numbers");
+    let output = Command::new(env!("CARGO_BIN_EXE_rdftab"))
+        .args(["--canonicalize-literals", db.to_str().unwrap()])
+        .stdin(fs::File::open(fixture_path("numbers.owl")).unwrap())
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{}", stderr);
+    assert!(stderr.contains("invalid xsd:boolean value 'TRUE'"), "{}", stderr);
+    assert!(stderr.contains("xsd:byte value '300' is out of range"), "{}", stderr);
+    let conn = Connection::open(&db).unwrap();
+    let values: Vec<(String, String)> = {
+        let mut stmt = conn.prepare("SELECT predicate, value FROM statements ORDER BY predicate").unwrap();
+        let rows = stmt.query_map(params![], |row| Ok((row.get(0)?, row.get(1)?))).unwrap();
+        rows.map(|row| row.unwrap()).collect()
+    };
+    let expected: Vec<(String, String)> = [
+        ("ex:count", "1"),
+        ("ex:depth", "0.5"),
+        ("ex:offset", "0"),
+        ("ex:open", "TRUE"),
+        ("ex:weight", "300"),
+        ("ex:width", "1.0"),
+    ].iter().map(|(p, v)| (p.to_string(), v.to_string())).collect();
+    assert_eq!(values, expected);
+    fs::remove_file(&db).unwrap();
+}
+
+#[test]
+fn stats_json_counts_the_import() {
+    let db = temp_db("stats");
+    let path = temp_db("stats").with_extension("json");
+    let (db, path) = (db.to_str().unwrap(), path.to_str().unwrap());
+    rdftab(&["--stats-json", path, db], Some("example.owl"));
+    let stats: serde_json::Value = serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap();
+    let count = |key: &str| stats.get(key).and_then(|value| value.as_u64());
+    assert_eq!(count("triples"), Some(9));
+    assert_eq!(count("inserted"), Some(9));
+    assert_eq!(count("stanzas"), Some(1));
+    assert_eq!(count("warnings"), Some(0));
+    fs::remove_file(db).unwrap();
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn json_lines_have_a_key_per_column() {
+    let db = temp_db("json");
+    let db = db.to_str().unwrap();
+    let keys = |line: &str| -> Vec<String> {
+        let value: serde_json::Value = serde_json::from_str(line).unwrap();
+        value.as_object().unwrap().iter().map(|(key, _)| key.clone()).collect()
+    };
+    let stdout = rdftab(&["--json", "--dry-run", db], Some("example.owl"));
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines.len(), 9);
+    for line in &lines {
+        assert_eq!(keys(line), vec!["datatype", "language", "object", "predicate", "stanza", "subject", "value"]);
+    }
+    assert!(lines.contains(&concat!(
+        r#"{"datatype":null,"language":null,"object":"owl:Class","#,
+        r#""predicate":"rdf:type","stanza":"ex:foo","subject":"ex:foo","value":null}"#
+    )));
+    assert!(lines.contains(&concat!(
+        r#"{"datatype":"xsd:int","language":null,"object":null,"#,
+        r#""predicate":"ex:size","stanza":"ex:foo","subject":"ex:foo","value":"123"}"#
+    )));
+    assert!(!Path::new(db).exists());
+
+    // Without --dry-run the statements are stored as well, and extra columns get keys.
+    let stdout = rdftab(&["--json", "--preserve-order", db], Some("example.owl"));
+    assert_eq!(stdout.lines().count(), 9);
+    assert!(stdout.lines().all(|line| keys(line).contains(&"seq".to_string())));
+    let conn = Connection::open(db).unwrap();
+    let count: i64 = conn.query_row("SELECT count(*) FROM statements", params![], |row| row.get(0)).unwrap();
+    assert_eq!(count, 9);
+    fs::remove_file(db).unwrap();
+}
+
+/// Export a database as sorted N-Triples, with blank nodes normalized.
+fn ntriples(db: &str) -> Vec<String> {
+    let mut lines: Vec<String> = rdftab(&["export", "--format", "ntriples", db], None).lines()
+        .map(|line| line.split(' ').map(normalize).collect::<Vec<_>>().join(" "))
+        .collect();
+    lines.sort();
+    lines
+}
+
+/// The distinct blank node labels in the N-Triples export of a database.
+fn blank_labels(db: &str) -> BTreeSet<String> {
+    rdftab(&["export", "--format", "ntriples", db], None).split([' ', '\n'])
+        .filter(|term| term.starts_with("_:"))
+        .map(String::from)
+        .collect()
+}
+
+#[test]
+fn gzipped_input_loads_like_plain_input() {
+    let gz = temp_db("example").with_extension("owl.gz");
+    let mut encoder = GzEncoder::new(fs::File::create(&gz).unwrap(), Compression::default());
+    encoder.write_all(fixture("example.owl").as_bytes()).unwrap();
+    encoder.finish().unwrap();
+
+    let load = |input: &str| {
+        let db = temp_db("gzip");
+        let db = db.to_str().unwrap();
+        rdftab(&[db, input], None);
+        let lines = ntriples(db);
+        fs::remove_file(db).unwrap();
+        lines
+    };
+    let plain = load(&fixture_path("example.owl"));
+    assert!(!plain.is_empty());
+    assert_eq!(load(gz.to_str().unwrap()), plain);
+
+    // Input on stdin is detected by its magic bytes too.
+    let db = temp_db("gzip-stdin");
+    let db = db.to_str().unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_rdftab"))
+        .args(["--quiet", db])
+        .stdin(fs::File::open(&gz).unwrap())
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(ntriples(db), plain);
+    fs::remove_file(db).unwrap();
+    fs::remove_file(&gz).unwrap();
+}
+
+#[test]
+fn thick_rows_export_like_thin_rows() {
+    let thin = temp_db("thin");
+    let thick = temp_db("thick");
+    let (thin, thick) = (thin.to_str().unwrap(), thick.to_str().unwrap());
+    // With this prefix a stored label could look like one that `unthicken` makes up.
+    rdftab(&["--bnode-prefix", "thick", thin], Some("thick.owl"));
+    rdftab(&["--bnode-prefix", "thick", "--thick", thick], Some("thick.owl"));
+    let conn = Connection::open(thick).unwrap();
+    let nested: i64 = conn.query_row(
+        "SELECT count(*) FROM statements WHERE substr(object, 1, 1) = '{'",
+        params![],
+        |row| row.get(0),
+    ).unwrap();
+    assert_eq!(nested, 1);
+    assert_eq!(ntriples(thick), ntriples(thin));
+    assert_eq!(blank_labels(thick).len(), blank_labels(thin).len());
+    fs::remove_file(thin).unwrap();
+    fs::remove_file(thick).unwrap();
+}
+
+#[test]
+fn reshorten_rewrites_thick_objects() {
+    let db = temp_db("reshorten-thick");
+    let db = db.to_str().unwrap();
+    rdftab(&["--thick", db], Some("example.owl"));
+    let before = ntriples(db);
+    {
+        let mut conn = Connection::open(db).unwrap();
+        store_prefixes(&mut conn, &[Prefix { prefix: "part".to_string(), base: "http://example.com/part-".to_string() }])
+            .unwrap();
+    }
+    rdftab(&["reshorten", db], None);
+    let conn = Connection::open(db).unwrap();
+    let object: String = conn.query_row(
+        "SELECT object FROM statements WHERE substr(object, 1, 1) = '{'",
+        params![],
+        |row| row.get(0),
+    ).unwrap();
+    assert!(object.contains("\"part:of\""), "{}", object);
+    assert_eq!(ntriples(db), before);
+    fs::remove_file(db).unwrap();
+}
+
+#[test]
+fn diff_reports_changed_and_added_stanzas() {
+    let old = temp_db("diff-old");
+    let new = temp_db("diff-new");
+    let (old, new) = (old.to_str().unwrap(), new.to_str().unwrap());
+    rdftab(&["--bnode-prefix", "old", old], Some("example.owl"));
+    rdftab(&["--bnode-prefix", "new", new], Some("example.owl"));
+    assert_eq!(rdftab(&["diff", old, new], None), "");
+
+    // The first restriction is the same, but the second one is new.
+    fs::remove_file(new).unwrap();
+    rdftab(&["--bnode-prefix", "new", new], Some("example-changed.owl"));
+    let output = rdftab(&["diff", old, new], None);
+    let mut lines: Vec<String> = output.lines()
+        .map(|line| line.split(' ').map(normalize).collect::<Vec<_>>().join(" "))
+        .collect();
+    lines.sort();
+    let mut expected = vec![
+        "",
+        "",
+        "<http://example.com/baz>",
+        "+ <http://example.com/baz> <http://www.w3.org/1999/02/22-rdf-syntax-ns#type> <http://www.w3.org/2002/07/owl#Class> .",
+        "<http://example.com/foo>",
+        "- <http://example.com/foo> <http://www.w3.org/2000/01/rdf-schema#label> \"Foo\" .",
+        "+ <http://example.com/foo> <http://www.w3.org/2000/01/rdf-schema#label> \"Foo!\" .",
+        "+ <http://example.com/foo> <http://www.w3.org/2000/01/rdf-schema#subClassOf> _:x .",
+        "+ _:x <http://www.w3.org/1999/02/22-rdf-syntax-ns#type> <http://www.w3.org/2002/07/owl#Restriction> .",
+        "+ _:x <http://www.w3.org/2002/07/owl#onProperty> <http://example.com/part-of> .",
+        "+ _:x <http://www.w3.org/2002/07/owl#someValuesFrom> <http://example.com/bar> .",
+    ];
+    expected.sort_unstable();
+    assert_eq!(lines, expected);
+    assert!(output.starts_with("<http://example.com/baz>\n"), "{}", output);
+    fs::remove_file(old).unwrap();
+    fs::remove_file(new).unwrap();
+}
+
+#[test]
+fn merge_rewrites_terms_for_the_target_prefixes() {
+    let source = temp_db("merge-source");
+    let target = temp_db("merge-target");
+    let (source, target) = (source.to_str().unwrap(), target.to_str().unwrap());
+    rdftab(&["--graph", "ex:a", source], Some("example.owl"));
+    {
+        let mut conn = Connection::open(target).unwrap();
+        store_prefixes(&mut conn, &[Prefix { prefix: "ex".to_string(), base: "http://other.org/".to_string() }])
+            .unwrap();
+    }
+    rdftab(&["merge", source, target], None);
+    assert_eq!(ntriples(target), ntriples(source));
+    let conn = Connection::open(target).unwrap();
+    let graph: String = conn.query_row("SELECT DISTINCT graph FROM statements", params![], |row| row.get(0)).unwrap();
+    assert_eq!(graph, "http://example.com/a");
+    // `ex:` means something else in the target, so the copied terms can't use it.
+    let remapped: i64 = conn.query_row(
+        "SELECT count(*) FROM statements WHERE subject = 'http://example.com/foo' AND predicate = 'http://example.com/link'
+           AND object = 'http://example.com/foo'",
+        params![],
+        |row| row.get(0),
+    ).unwrap();
+    assert_eq!(remapped, 1);
+    let unchanged: i64 = conn.query_row(
+        "SELECT count(*) FROM statements WHERE substr(subject, 1, 3) = 'ex:' OR substr(object, 1, 3) = 'ex:'",
+        params![],
+        |row| row.get(0),
+    ).unwrap();
+    assert_eq!(unchanged, 0);
+    fs::remove_file(source).unwrap();
+    fs::remove_file(target).unwrap();
+}
+
+#[test]
+fn merge_skips_duplicates_within_the_source() {
+    let source = temp_db("merge-twice-source");
+    let target = temp_db("merge-twice-target");
+    let (source, target) = (source.to_str().unwrap(), target.to_str().unwrap());
+    rdftab(&[source], Some("example.owl"));
+    rdftab(&[source], Some("example.owl"));
+    rdftab(&["merge", source, target], None);
+    rdftab(&["merge", source, target], None);
+    let conn = Connection::open(target).unwrap();
+    let named: i64 = conn.query_row(
+        "SELECT count(*) FROM statements WHERE subject = 'ex:foo' AND predicate != 'rdfs:subClassOf'",
+        params![],
+        |row| row.get(0),
+    ).unwrap();
+    assert_eq!(named, 5);
+    let indexes: i64 = conn.query_row(
+        "SELECT count(*) FROM sqlite_master WHERE type = 'index' AND name = 'rdftab_merge'",
+        params![],
+        |row| row.get(0),
+    ).unwrap();
+    assert_eq!(indexes, 0);
+    fs::remove_file(source).unwrap();
+    fs::remove_file(target).unwrap();
+}
+
+#[test]
+fn large_batches_stay_under_the_parameter_limit() {
+    let classes: String = (0..200)
+        .map(|i| format!("  <owl:Class rdf:about=\"http://example.com/c{}\"/>\n", i))
+        .collect();
+    let input = temp_db("batches").with_extension("owl");
+    fs::write(&input, format!(r#"<?xml version="1.0"?>
+<rdf:RDF xmlns:owl="http://www.w3.org/2002/07/owl#"
+     xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+  <owl:Ontology rdf:about="http://example.com/batches"/>
+{}</rdf:RDF>
+"#, classes)).unwrap();
+    let db = temp_db("batches");
+    let (db, input) = (db.to_str().unwrap(), input.to_str().unwrap());
+    rdftab(&["--emit-kind", "--preserve-order", "--graph", "ex:a", "--batch-size", "500", db, input], None);
+    let conn = Connection::open(db).unwrap();
+    let count: i64 = conn.query_row("SELECT count(*) FROM statements", params![], |row| row.get(0)).unwrap();
+    assert_eq!(count, 201);
+    fs::remove_file(db).unwrap();
+    fs::remove_file(input).unwrap();
+}
+
+#[test]
+fn csv_rows_are_numbered_and_checked() {
+    let db = temp_db("csv");
+    let db = db.to_str().unwrap();
+    rdftab(&["--format", "csv", "--preserve-order", db, &fixture_path("statements.csv")], None);
+    let conn = Connection::open(db).unwrap();
+    let mut stmt = conn.prepare(
+        "SELECT subject, predicate, coalesce(datatype, ''), coalesce(language, ''), CAST(seq AS TEXT)
+         FROM statements ORDER BY seq"
+    ).unwrap();
+    let rows: Vec<Vec<String>> = stmt
+        .query_map(params![], |row| (0..5).map(|i| row.get(i)).collect())
+        .unwrap()
+        .map(|row| row.unwrap())
+        .collect();
+    assert_eq!(rows, vec![
+        vec!["ex:foo", "rdfs:label", "", "en", "1"],
+        vec!["ex:foo", "rdf:type", "", "", "2"],
+        vec!["ex:bar", "rdf:type", "", "", "3"],
+    ]);
+    fs::remove_file(db).unwrap();
+}
+
+#[test]
+fn strictness_and_warnings_belong_to_each_load() {
+    let load = |options: &Options| {
+        let mut sink = SqliteSink::new(Connection::open_in_memory().unwrap(), Duration::from_secs(1)).unwrap();
+        let inputs = vec![(fs::File::open(fixture_path("statements.csv")).unwrap(), "statements.csv".to_string())];
+        load_csv(inputs, b',', &prefixes(), options, &mut sink)
+    };
+    let lenient = Options { format: "csv".to_string(), ..Options::default() };
+    let strict = Options { format: "csv".to_string(), strict: true, ..Options::default() };
+    load(&lenient).unwrap();
+    assert_eq!(exit_code(load(&strict).unwrap_err().as_ref()), 4);
+    assert_eq!(lenient.counters.warnings.load(Ordering::Relaxed), 1);
+    assert_eq!(strict.counters.warnings.load(Ordering::Relaxed), 0);
+}
+
+#[test]
+fn incomplete_csv_rows_are_skipped_and_summarized() {
+    let path = temp_db("incomplete").with_extension("csv");
+    let mut csv = String::from("subject,predicate,object\nex:foo,rdf:type,owl:Class\n");
+    for i in 0..12 {
+        csv.push_str(if i % 2 == 0 { ",rdf:type,owl:Class\n" } else { "ex:foo,,owl:Class\n" });
+    }
+    csv.push_str("ex:bar,rdf:type,owl:Class\n");
+    fs::write(&path, csv).unwrap();
+    let path = path.to_str().unwrap();
+    let db = temp_db("incomplete");
+    let db = db.to_str().unwrap();
+    let run = |strict: bool| {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_rdftab"));
+        command.args(["--format", "csv"]);
+        if strict {
+            command.arg("--strict");
+        }
+        command.args([db, path]).output().unwrap()
+    };
+
+    let output = run(false);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{}", stderr);
+    // Only the first few lines are listed.
+    assert!(stderr.contains("skipped 12 rows missing a subject or predicate, on lines 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, ..."),
+        "{}", stderr);
+    let conn = Connection::open(db).unwrap();
+    let subjects: Vec<String> = {
+        let mut stmt = conn.prepare("SELECT subject FROM statements ORDER BY subject").unwrap();
+        let rows = stmt.query_map(params![], |row| row.get(0)).unwrap();
+        rows.map(|row| row.unwrap()).collect()
+    };
+    assert_eq!(subjects, vec!["ex:bar", "ex:foo"]);
+    fs::remove_file(db).unwrap();
+
+    let output = run(true);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(4), "{}", stderr);
+    assert!(stderr.contains(":3: missing subject or predicate"), "{}", stderr);
+    let _ = fs::remove_file(db);
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn fast_loads_restore_the_journal_mode() {
+    let db = temp_db("fast");
+    let db = db.to_str().unwrap();
+    let journal_mode = || -> String {
+        let conn = Connection::open(db).unwrap();
+        conn.query_row("PRAGMA journal_mode", params![], |row| row.get(0)).unwrap()
+    };
+    rdftab(&["--fast", db], Some("example.owl"));
+    assert_eq!(journal_mode(), "delete");
+    assert!(!Path::new(&format!("{}-wal", db)).exists());
+    assert_eq!(ntriples(db).len(), 9);
+
+    // A database that was already in WAL mode stays that way.
+    Connection::open(db).unwrap().execute_batch("PRAGMA journal_mode = WAL;").unwrap();
+    rdftab(&["--fast", db], Some("example.owl"));
+    assert_eq!(journal_mode(), "wal");
+    fs::remove_file(db).unwrap();
+}
+
+#[test]
+fn locked_databases_are_waited_for() {
+    let db = temp_db("busy");
+    let db = db.to_str().unwrap();
+    let lock = Connection::open(db).unwrap();
+    lock.execute_batch("CREATE TABLE other (x); BEGIN EXCLUSIVE; INSERT INTO other VALUES (1);").unwrap();
+    let spawn = |timeout_ms: &str| {
+        Command::new(env!("CARGO_BIN_EXE_rdftab"))
+            .args(["--quiet", "--busy-timeout-ms", timeout_ms, db])
+            .stdin(fs::File::open(fixture_path("example.owl")).unwrap())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap()
+    };
+
+    // While the lock is held, the load waits for the whole timeout and then fails.
+    let start = Instant::now();
+    let output = spawn("500").wait_with_output().unwrap();
+    assert!(start.elapsed() >= Duration::from_millis(500), "gave up after {:?}", start.elapsed());
+    assert_eq!(output.status.code(), Some(5), "{}", String::from_utf8_lossy(&output.stderr));
+
+    // When the lock is released in time, the load goes ahead.
+    let child = spawn("10000");
+    thread::sleep(Duration::from_millis(500));
+    lock.execute_batch("COMMIT").unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(ntriples(db).len(), 9);
+    drop(lock);
+    fs::remove_file(db).unwrap();
+}
+
+#[test]
+fn import_metadata_is_written_with_the_statements() {
+    let db = temp_db("import-metadata");
+    let db = db.to_str().unwrap();
+    rdftab(&[db], Some("example.owl"));
+    let status = Command::new(env!("CARGO_BIN_EXE_rdftab"))
+        .args(["--quiet", db])
+        .stdin(fs::File::open(fixture_path("malformed.owl")).unwrap())
+        .status().unwrap();
+    assert_eq!(status.code(), Some(4));
+
+    let conn = Connection::open(db).unwrap();
+    let columns: Vec<String> = {
+        let mut stmt = conn.prepare("PRAGMA table_info(import_metadata)").unwrap();
+        let rows = stmt.query_map(params![], |row| row.get(1)).unwrap();
+        rows.map(|column| column.unwrap()).collect()
+    };
+    assert_eq!(columns, vec!["source", "imported", "version", "statements"]);
+    // The failed load rolled back its metadata row along with its statements.
+    let rows: Vec<(String, String, i64)> = {
+        let mut stmt = conn.prepare("SELECT source, version, statements FROM import_metadata").unwrap();
+        let rows = stmt.query_map(params![], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?))).unwrap();
+        rows.map(|row| row.unwrap()).collect()
+    };
+    assert_eq!(rows, vec![("stdin".to_string(), env!("CARGO_PKG_VERSION").to_string(), 9)]);
+    let statements: i64 = conn.query_row("SELECT count(*) FROM statements", params![], |row| row.get(0)).unwrap();
+    assert_eq!(statements, 9);
+    fs::remove_file(db).unwrap();
+}
+
+#[test]
+fn commit_every_commits_during_the_load() {
+    let db = temp_db("commit-every");
+    let db = db.to_str().unwrap();
+    let input = fixture_path("example.owl");
+    let output = Command::new(env!("CARGO_BIN_EXE_rdftab"))
+        .args(["--verbose", "--batch-size", "2", "--commit-every", "4", db, &input])
+        .output().unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{}", stderr);
+    assert_eq!(stderr.matches("Committing after").count(), 2, "{}", stderr);
+    let conn = Connection::open(db).unwrap();
+    let (source, statements): (String, i64) = conn.query_row(
+        "SELECT source, statements FROM import_metadata",
+        params![],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    ).unwrap();
+    assert_eq!(source, input);
+    assert_eq!(statements, 9);
+    fs::remove_file(db).unwrap();
+}
+
+#[test]
+fn wrapped_database_errors_keep_their_exit_code() {
+    let err = RdftabError::Context("Failed to insert".to_string(), Box::new(rusqlite::Error::QueryReturnedNoRows));
+    assert_eq!(exit_code(&err), 5);
+    let err: Box<dyn std::error::Error> = Box::new(RdftabError::Context("Retrying".to_string(), Box::new(err)));
+    assert_eq!(exit_code(err.as_ref()), 5);
+}
+
+#[test]
+fn reshorten_applies_a_new_prefix() {
+    let db = temp_db("reshorten");
+    let db = db.to_str().unwrap();
+    rdftab(&["--no-shorten", db], Some("example.owl"));
+    {
+        let mut conn = Connection::open(db).unwrap();
+        store_prefixes(&mut conn, &[Prefix { prefix: "ex".to_string(), base: "http://example.com/".to_string() }])
+            .unwrap();
+    }
+    rdftab(&["reshorten", db], None);
+    let conn = Connection::open(db).unwrap();
+    let subjects: Vec<String> = {
+        let mut stmt = conn.prepare("SELECT DISTINCT subject FROM statements WHERE substr(subject, 1, 2) != '_:'").unwrap();
+        let rows = stmt.query_map(params![], |row| row.get(0)).unwrap();
+        rows.map(|subject| subject.unwrap()).collect()
+    };
+    assert_eq!(subjects, vec!["ex:foo"]);
+    let objects: i64 = conn.query_row(
+        "SELECT count(*) FROM statements WHERE object IN ('ex:foo', 'ex:bar', 'ex:part-of')",
+        params![],
+        |row| row.get(0),
+    ).unwrap();
+    assert_eq!(objects, 3);
+    fs::remove_file(db).unwrap();
+}
+
+#[test]
+fn in_memory_loads_every_argument() {
+    let db = temp_db("in-memory");
+    let db = db.to_str().unwrap();
+    rdftab(&["--in-memory", "--save", db, &fixture_path("example.owl"), &fixture_path("bom.owl")], None);
+    assert_eq!(ntriples(db).len(), 11);
+    fs::remove_file(db).unwrap();
+}
+
+#[test]
+fn graph_dot_draws_named_subclass_edges() {
+    let db = temp_db("graph-dot");
+    let db = db.to_str().unwrap();
+    rdftab(&[db], Some("hierarchy.owl"));
+    assert_eq!(rdftab(&["graph", "--dot", db], None), "digraph {
+  rankdir=BT;
+  \"ex:animal\" [label=\"Animal\"];
+  \"ex:cat\" [label=\"ex:cat\"];
+  \"ex:dog\" [label=\"Dog\"];
+  \"ex:cat\" -> \"ex:animal\";
+  \"ex:dog\" -> \"ex:animal\";
+}
+");
+    fs::remove_file(db).unwrap();
+}
+
+#[test]
+fn graph_reads_unshortened_databases() {
+    let db = temp_db("graph-no-shorten");
+    let db = db.to_str().unwrap();
+    rdftab(&["--no-shorten", db], Some("hierarchy.owl"));
+    let dot = rdftab(&["graph", "--dot", db], None);
+    assert!(dot.contains("  \"http://example.com/dog\" [label=\"Dog\"];\n"), "{}", dot);
+    assert!(dot.contains("  \"http://example.com/cat\" -> \"http://example.com/animal\";\n"), "{}", dot);
+    fs::remove_file(db).unwrap();
+}
+
+#[test]
+fn graph_mermaid_numbers_the_nodes() {
+    let db = temp_db("graph-mermaid");
+    let db = db.to_str().unwrap();
+    rdftab(&[db], Some("hierarchy.owl"));
+    assert_eq!(rdftab(&["graph", "--mermaid", db], None), "graph BT
+  n0[\"Animal\"]
+  n1[\"ex:cat\"]
+  n2[\"Dog\"]
+  n1 --> n0
+  n2 --> n0
+");
+    fs::remove_file(db).unwrap();
+}
+
+#[test]
+fn malformed_rdfxml_is_a_parse_error() {
+    let db = temp_db("malformed");
+    let output = Command::new(env!("CARGO_BIN_EXE_rdftab"))
+        .arg("--quiet").arg(&db)
+        .stdin(fs::File::open(fixture_path("malformed.owl")).unwrap())
+        .output().unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(4), "{}", stderr);
+    assert!(stderr.contains("error at line"), "{}", stderr);
+    assert!(!stderr.contains("panicked"), "{}", stderr);
+    fs::remove_file(&db).unwrap();
+}
+
+#[test]
+fn limit_stops_parsing_after_n_stanzas() {
+    let db = temp_db("limit");
+    let db = db.to_str().unwrap();
+    // The third stanza is malformed, so this only works if parsing stops before it.
+    rdftab(&["--limit", "2", db], Some("limit.owl"));
+    let conn = Connection::open(db).unwrap();
+    let subjects: Vec<(String, i64)> = {
+        let mut stmt = conn.prepare("SELECT subject, count(*) FROM statements GROUP BY subject ORDER BY subject").unwrap();
+        let rows = stmt.query_map(params![], |row| Ok((row.get(0)?, row.get(1)?))).unwrap();
+        rows.map(|row| row.unwrap()).collect()
+    };
+    assert_eq!(subjects, vec![("ex:a".to_string(), 2), ("ex:b".to_string(), 2)]);
+    fs::remove_file(db).unwrap();
+}
+
+#[test]
+fn subject_prefix_keeps_the_blank_nodes_of_kept_stanzas() {
+    let db = temp_db("subject-prefix");
+    let db = db.to_str().unwrap();
+    rdftab(&["--subject-prefix", "ex:", db], Some("subjects.owl"));
+    let conn = Connection::open(db).unwrap();
+    let rows: Vec<(String, String, String)> = {
+        let mut stmt = conn.prepare("SELECT stanza, subject, predicate FROM statements ORDER BY rowid").unwrap();
+        let rows = stmt.query_map(params![], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?))).unwrap();
+        rows.map(|row| row.unwrap()).collect()
+    };
+    assert_eq!(rows.len(), 5);
+    assert!(rows.iter().all(|(stanza, _, _)| stanza == "ex:foo"));
+    assert_eq!(rows.iter().filter(|(_, subject, _)| subject.starts_with("_:")).count(), 3);
+    fs::remove_file(db).unwrap();
+}
+
+#[test]
+fn excluded_predicates_are_dropped_before_annotations_are_placed() {
+    let db = temp_db("exclude");
+    let path = temp_db("exclude").with_extension("json");
+    let (db, path) = (db.to_str().unwrap(), path.to_str().unwrap());
+    rdftab(&["--exclude-predicate", "owl:annotatedSource", "--stats-json", path, db], Some("nested-annotation.owl"));
+    let conn = Connection::open(db).unwrap();
+    let count = |sql: &str| -> i64 { conn.query_row(sql, params![], |row| row.get(0)).unwrap() };
+    assert_eq!(count("SELECT count(*) FROM statements WHERE predicate = 'owl:annotatedSource'"), 0);
+    // Without their owl:annotatedSource, the axioms aren't part of the ex:foo stanza.
+    assert_eq!(count("SELECT count(*) FROM statements WHERE stanza = 'ex:foo'"), 2);
+    assert_eq!(count("SELECT count(*) FROM statements"), 10);
+    let stats: serde_json::Value = serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap();
+    assert_eq!(stats.get("excluded").and_then(|value| value.as_u64()), Some(2));
+    assert_eq!(stats.get("triples").and_then(|value| value.as_u64()), Some(12));
+    fs::remove_file(db).unwrap();
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn validate_iris_warns_or_stops() {
+    let db = temp_db("validate-iris");
+    let run = |strict: bool| {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_rdftab"));
+        command.arg("--validate-iris");
+        if strict {
+            command.arg("--strict");
+        }
+        command.arg(&db).stdin(fs::File::open(fixture_path("malformed-iri.owl")).unwrap()).output().unwrap()
+    };
+    let output = run(false);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{}", stderr);
+    assert!(stderr.contains("found 1 malformed IRIs"), "{}", stderr);
+    assert!(stderr.contains("<http://example.com/not an iri/size>"), "{}", stderr);
+    fs::remove_file(&db).unwrap();
+
+    let output = run(true);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(4), "{}", stderr);
+    assert!(stderr.contains("malformed IRI <http://example.com/not an iri/size>"), "{}", stderr);
+    let _ = fs::remove_file(&db);
+}
+
+#[test]
+fn relative_iris_resolve_against_the_base_option() {
+    let db = temp_db("base");
+    let db = db.to_str().unwrap();
+    rdftab(&["--base", "http://example.com/", db], Some("relative.owl"));
+    let conn = Connection::open(db).unwrap();
+    let (subject, object): (String, String) = conn.query_row(
+        "SELECT subject, object FROM statements WHERE predicate = 'rdfs:subClassOf'",
+        params![],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    ).unwrap();
+    assert_eq!(subject, "ex:foo");
+    assert_eq!(object, "http://example.com/#bar");
+    fs::remove_file(db).unwrap();
+}
+
+#[test]
+fn trim_literals_is_needed_to_trim_xsd_strings() {
+    let values = |args: &[&str]| -> Vec<(String, String)> {
+        let db = temp_db("trim-literals");
+        let db = db.to_str().unwrap();
+        let mut args = args.to_vec();
+        args.push(db);
+        rdftab(&args, Some("whitespace.owl"));
+        let conn = Connection::open(db).unwrap();
+        let values = {
+            let mut stmt = conn.prepare("SELECT predicate, value FROM statements ORDER BY predicate, value").unwrap();
+            let rows = stmt.query_map(params![], |row| Ok((row.get(0)?, row.get(1)?))).unwrap();
+            rows.map(|row| row.unwrap()).collect()
+        };
+        fs::remove_file(db).unwrap();
+        values
+    };
+    let pairs = |pairs: &[(&str, &str)]| -> Vec<(String, String)> {
+        pairs.iter().map(|(p, v)| (p.to_string(), v.to_string())).collect()
+    };
+    // The XML literal keeps its whitespace either way.
+    let (markup, rest): (Vec<_>, Vec<_>) = values(&[]).into_iter().partition(|(p, _)| p == "rdfs:comment");
+    assert!(markup[0].1.starts_with(' ') && markup[0].1.ends_with(' '), "{:?}", markup);
+    // xsd:string whitespace is significant, so it's kept unless asked for.
+    assert_eq!(rest, pairs(&[("ex:code", "  A1 "), ("ex:size", " 12 "), ("rdfs:label", " Foo "), ("rdfs:label", " Fou ")]));
+    let (trimmed_markup, trimmed): (Vec<_>, Vec<_>) = values(&["--trim-literals"]).into_iter().partition(|(p, _)| p == "rdfs:comment");
+    assert_eq!(trimmed_markup, markup);
+    assert_eq!(trimmed, pairs(&[("ex:code", "A1"), ("ex:size", "12"), ("rdfs:label", "Foo"), ("rdfs:label", "Fou")]));
+}
+
+#[test]
+fn prefixes_with_the_same_base_are_chosen_by_name() {
+    let mut conn = Connection::open_in_memory().unwrap();
+    for prefix in &["example", "ex"] {
+        store_prefixes(&mut conn, &[Prefix { prefix: prefix.to_string(), base: "http://example.com/".to_string() }]).unwrap();
+    }
+    let prefixes = get_prefixes(&mut conn).unwrap();
+    assert_eq!(shorten(&prefixes, "http://example.com/foo"), "ex:foo");
+}
+
+#[test]
+fn shorten_only_matches_at_a_local_name() {
+    let foo = vec![Prefix { prefix: "foo".to_string(), base: "http://example.com/foo".to_string() }];
+    assert_eq!(shorten(&foo, "http://example.com/foobar"), "foo:bar");
+    assert_eq!(shorten(&foo, "http://example.com/foobar/baz"), "http://example.com/foobar/baz");
+
+    let urn = vec![Prefix { prefix: "x".to_string(), base: "urn:x:".to_string() }];
+    assert_eq!(shorten(&urn, "urn:x:aurn:x:b"), "x:aurn:x:b");
+}
+
+#[test]
+fn expand_inverts_shorten() {
+    let prefixes = prefixes();
+    for iri in &[
+        "http://example.com/foo",
+        "http://www.w3.org/2000/01/rdf-schema#label",
+        "http://www.w3.org/2001/XMLSchema#string",
+        "http://example.com/nested/foo",
+        "http://unknown.org/foo",
+    ] {
+        assert_eq!(expand(&prefixes, &shorten(&prefixes, iri)), *iri);
+    }
+    assert_eq!(expand(&prefixes, "<http://unknown.org/foo>"), "http://unknown.org/foo");
+    assert_eq!(expand(&prefixes, "Foo"), "Foo");
+}
+
+#[test]
+fn the_empty_prefix_is_the_default_namespace() {
+    let mut conn = Connection::open_in_memory().unwrap();
+    create_prefix_table(&conn).unwrap();
+    conn.execute("INSERT INTO prefix VALUES (NULL, 'http://example.com/')", params![]).unwrap();
+    let prefixes = get_prefixes(&mut conn).unwrap();
+    assert_eq!(prefixes.len(), 1);
+    assert_eq!(prefixes[0].prefix, "");
+    assert_eq!(shorten(&prefixes, "http://example.com/Foo"), ":Foo");
+    assert_eq!(expand(&prefixes, ":Foo"), "http://example.com/Foo");
+
+    let mut input = std::io::Cursor::new(
+        "<rdf:RDF xmlns=\"http://example.com/\" xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">"
+    );
+    let (_, namespaces) = read_namespaces(&mut input).unwrap();
+    let default: Vec<&str> = namespaces.iter().filter(|p| p.prefix.is_empty()).map(|p| p.base.as_str()).collect();
+    assert_eq!(default, vec!["http://example.com/"]);
+}
+
+#[test]
+fn prefixes_prints_the_prefix_table_as_turtle() {
+    let db = temp_db("prefixes");
+    let db = db.to_str().unwrap();
+    {
+        let mut conn = Connection::open(db).unwrap();
+        create_prefix_table(&conn).unwrap();
+        conn.execute("INSERT INTO prefix VALUES (NULL, 'http://example.com/#')", params![]).unwrap();
+        store_prefixes(&mut conn, &[
+            Prefix { prefix: "obo".to_string(), base: "http://purl.obolibrary.org/obo/".to_string() },
+            Prefix { prefix: "ex".to_string(), base: "http://example.com/".to_string() },
+        ]).unwrap();
+    }
+    assert_eq!(rdftab(&["prefixes", db], None), concat!(
+        "@prefix : <http://example.com/#> .\n",
+        "@prefix ex: <http://example.com/> .\n",
+        "@prefix obo: <http://purl.obolibrary.org/obo/> .\n",
+    ));
+    fs::remove_file(db).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_rdftab")).args(["prefixes", db]).output().unwrap();
+    assert_eq!(output.status.code(), Some(3));
+    assert!(!Path::new(db).exists());
+}
+
+#[test]
+fn prefixes_json_loads_a_json_ld_context() {
+    let path = temp_db("context").with_extension("json");
+    fs::write(&path, r#"{"@context": {
+        "@vocab": "http://example.com/vocab/",
+        "ex": "http://example.com/",
+        "obo": "http://purl.obolibrary.org/obo/",
+        "term": {"@id": "ex:term"}
+    }}"#).unwrap();
+    let path = path.to_str().unwrap();
+    let mut prefixes: Vec<(String, String)> = read_prefix_json(path).unwrap().into_iter()
+        .map(|prefix| (prefix.prefix, prefix.base))
+        .collect();
+    prefixes.sort();
+    assert_eq!(prefixes, vec![
+        ("ex".to_string(), "http://example.com/".to_string()),
+        ("obo".to_string(), "http://purl.obolibrary.org/obo/".to_string()),
+    ]);
+
+    // A prefix that changes an existing one is replaced, with a warning.
+    let db = temp_db("prefixes-json");
+    let db = db.to_str().unwrap();
+    {
+        let mut conn = Connection::open(db).unwrap();
+        store_prefixes(&mut conn, &[Prefix { prefix: "ex".to_string(), base: "http://other.org/".to_string() }])
+            .unwrap();
+    }
+    let output = Command::new(env!("CARGO_BIN_EXE_rdftab"))
+        .args(["--prefixes-json", path, db])
+        .stdin(fs::File::open(fixture_path("example.owl")).unwrap())
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{}", stderr);
+    assert!(stderr.contains("replacing prefix 'ex' <http://other.org/> with <http://example.com/>"), "{}", stderr);
+    let conn = Connection::open(db).unwrap();
+    let subjects: BTreeSet<String> = {
+        let mut stmt = conn.prepare("SELECT stanza FROM statements").unwrap();
+        let rows = stmt.query_map(params![], |row| row.get(0)).unwrap();
+        rows.map(|row| row.unwrap()).collect()
+    };
+    assert_eq!(subjects, vec!["ex:foo".to_string()].into_iter().collect());
+    fs::remove_file(db).unwrap();
+
+    fs::write(path, "[\"ex\"]").unwrap();
+    assert_eq!(exit_code(read_prefix_json(path).unwrap_err().as_ref()), 4);
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn the_db_defaults_to_rdftab_db() {
+    let db = temp_db("env");
+    let other = temp_db("env-other");
+    let (db, other) = (db.to_str().unwrap(), other.to_str().unwrap());
+    let run = |args: &[&str], env_db: Option<&str>| {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_rdftab"));
+        command.arg("--quiet").args(args).env_remove("RDFTAB_DB");
+        if let Some(env_db) = env_db {
+            command.env("RDFTAB_DB", env_db);
+        }
+        command.stdin(fs::File::open(fixture_path("example.owl")).unwrap()).output().unwrap()
+    };
+    assert!(run(&[], Some(db)).status.success());
+    assert!(Path::new(db).exists());
+    let output = run(&["export", "--format", "ntriples"], Some(db));
+    assert_eq!(String::from_utf8_lossy(&output.stdout).lines().count(), 9);
+
+    // A DB argument takes precedence.
+    assert!(run(&[other], Some(db)).status.success());
+    assert!(Path::new(other).exists());
+    assert_eq!(ntriples(other), ntriples(db));
+    fs::remove_file(db).unwrap();
+    fs::remove_file(other).unwrap();
+
+    // Without either, or with an empty variable, it's a usage error.
+    for env_db in &[None, Some("")] {
+        let output = run(&[], *env_db);
+        assert_eq!(output.status.code(), Some(2));
+        assert!(String::from_utf8_lossy(&output.stderr).contains("RDFTAB_DB is not set"));
+    }
+}
+
+#[test]
+fn prefix_tables_are_read_as_csv() {
+    let path = env::temp_dir().join(format!("rdftab-prefixes-{}.csv", std::process::id()));
+    fs::write(&path, "prefix,base\n# a comment\nex,\"http://example.com/a,b/\"\n").unwrap();
+    let path = path.to_str().unwrap();
+    let prefixes = read_prefix_file(path).unwrap();
+    assert_eq!(prefixes.len(), 1);
+    assert_eq!((prefixes[0].prefix.as_str(), prefixes[0].base.as_str()), ("ex", "http://example.com/a,b/"));
+    fs::remove_file(path).unwrap();
+    assert_eq!(exit_code(read_prefix_file(path).unwrap_err().as_ref()), 3);
+}
+
+#[test]
+fn byte_order_marks_are_skipped() {
+    assert_eq!(rdftab(&["count", &fixture_path("bom.owl")], None).trim(), "2");
+    let db = temp_db("bom");
+    let db = db.to_str().unwrap();
+    rdftab(&[db], Some("bom.owl"));
+    let ntriples = rdftab(&["export", "--format", "ntriples", db], None);
+    assert_eq!(ntriples.lines().count(), 2);
+    fs::remove_file(db).unwrap();
+}
+
+#[test]
+fn deep_blank_node_chains_stop_at_max_nesting() {
+    let conn = Connection::open_in_memory().unwrap();
+    create_statements_table(&conn).unwrap();
+    let depth = MAX_NESTING * 4;
+    for i in 0..depth {
+        let subject = if i == 0 { "ex:foo".to_string() } else { format!("_:b{}", i) };
+        conn.execute(
+            "INSERT INTO statements (stanza, subject, predicate, object) VALUES ('ex:foo', ?1, 'rdf:rest', ?2)",
+            params![subject, format!("_:b{}", i + 1)],
+        ).unwrap();
+    }
+    let mut json = subject_to_json(&conn, "ex:foo").unwrap();
+    let mut nesting = 0;
+    loop {
+        let object = json.get("rdf:rest")
+            .and_then(|objects| objects.as_array())
+            .and_then(|objects| objects.first())
+            .and_then(|cell| cell.get("object"))
+            .cloned()
+            .unwrap();
+        if !object.is_object() {
+            break;
+        }
+        json = object;
+        nesting += 1;
+    }
+    assert_eq!(nesting, MAX_NESTING - 1);
+}